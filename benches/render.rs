@@ -0,0 +1,75 @@
+//! Rendering the blog index of the default theme through `render::render`,
+//! with a fresh `RegistryCache` per request, as before `render::registry`
+//! kept the compiled templates, and with the theme's own cache.
+//!
+//! Run with `cargo bench --bench render`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use myweb::render::{render, RegistryCache};
+use myweb::settings::SiteSettings;
+use myweb::theme::ActiveTheme;
+use serde_json::{json, Map, Value};
+use std::path::Path;
+use tokio::runtime::Runtime;
+
+/// Components the blog index picks, see `public::blog_index`.
+const OVERRIDES: [(&str, &str); 2] = [("parent", "layout"), ("article", "article_preview")];
+
+fn data() -> Map<String, Value> {
+    let articles: Vec<Value> = (0..10)
+        .map(|i| {
+            json!({
+                "title": format!("Article {}", i),
+                "url": format!("/blog/article_{}", i),
+                "date": "2024-07-01",
+                "body": "A short preview of the article, long enough to be a paragraph.",
+                "tags": [{ "name": "rust", "url": "/tags/rust" }],
+            })
+        })
+        .collect();
+    let data = json!({
+        "parent": "layout",
+        "page_title": "Blog",
+        "layout_min": false,
+        "tag_cloud": [{ "name": "rust", "url": "/tags/rust", "count": 10, "weight": 5 }],
+        "articles": articles,
+        "pagination": { "page": 1, "total_pages": 3, "previous": null, "next": "/blog?page=2" },
+    });
+    match data {
+        Value::Object(data) => data,
+        _ => unreachable!(),
+    }
+}
+
+fn blog_index(c: &mut Criterion) {
+    let runtime = Runtime::new().expect("tokio runtime");
+    let theme_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("theme");
+    let theme = runtime
+        .block_on(ActiveTheme::load(&theme_dir, "default"))
+        .expect("default theme");
+    let site = SiteSettings::default();
+    let data = data();
+    let mut group = c.benchmark_group("blog_index");
+    group.bench_function("registry_per_request", |b| {
+        b.iter(|| {
+            let theme = ActiveTheme {
+                registries: RegistryCache::new(false),
+                ..theme.clone()
+            };
+            runtime
+                .block_on(render("default", &theme, &OVERRIDES, &site, black_box(data.clone())))
+                .unwrap()
+        })
+    });
+    group.bench_function("cached_registry", |b| {
+        b.iter(|| {
+            runtime
+                .block_on(render("default", &theme, &OVERRIDES, &site, black_box(data.clone())))
+                .unwrap()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, blog_index);
+criterion_main!(benches);
@@ -1,16 +1,31 @@
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
 use ulid::Ulid;
 
+use crate::db::{FileData, Store};
 use crate::json::Json;
+use crate::Error;
 
+pub type AccountStore = FileData<Ulid, Account>;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Account {
     pub id: Ulid,
     pub username: String,
     pub email: String,
+    /// Argon2 PHC string of the password, never the password itself.
     pub secret: String,
     pub privilege: Privilege,
+    /// Replaces the privilege's default content authority when set.
+    #[serde(default)]
+    pub content_authority: Option<ContentAuthority>,
     pub google_auth: Option<Json>,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Privilege {
     Admin,
     Moderator,
@@ -22,9 +37,11 @@ pub struct Privileges {
     pub server_config: bool,
     pub site_config: bool,
     pub content_authority: ContentAuthority,
+    /// Not enforced yet, nothing takes comments.
     pub comment: bool,
 }
 
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum ContentAuthority {
     All,
     Oneself,
@@ -39,7 +56,30 @@ pub trait GetPrivileges {
 
 impl GetPrivileges for Account {
     fn get_privileges(&self) -> Privileges {
-        self.privilege.get_privileges()
+        let mut privileges = self.privilege.get_privileges();
+        if let Some(content_authority) = &self.content_authority {
+            privileges.content_authority = content_authority.clone();
+        }
+        privileges
+    }
+}
+
+impl ContentAuthority {
+    /// Whether `account` may modify content `id` owned by `owner`.
+    ///
+    /// `Except` and `Specific` lists may hold article ids or owner account ids.
+    /// New content has no id yet and is owned by the account creating it.
+    pub fn allows(&self, account: &Account, id: Option<Ulid>, owner: Option<Ulid>) -> bool {
+        let listed = |ids: &[Ulid]| {
+            id.is_some_and(|id| ids.contains(&id)) || owner.is_some_and(|o| ids.contains(&o))
+        };
+        match self {
+            ContentAuthority::All => true,
+            ContentAuthority::Oneself => owner == Some(account.id),
+            ContentAuthority::None => false,
+            ContentAuthority::Except(ids) => !listed(ids),
+            ContentAuthority::Specific(ids) => listed(ids),
+        }
     }
 }
 
@@ -73,3 +113,59 @@ impl GetPrivileges for Privilege {
         }
     }
 }
+
+pub fn hash_password(password: &str) -> Result<String, Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| format!("Failed to hash password: {}", e).into())
+}
+
+/// A random password for bootstrapped accounts.
+pub fn generate_password() -> String {
+    SaltString::generate(&mut OsRng).to_string()
+}
+
+impl Account {
+    pub fn new(
+        username: String,
+        email: String,
+        password: &str,
+        privilege: Privilege,
+    ) -> Result<Account, Error> {
+        Ok(Account {
+            id: Ulid::new(),
+            username,
+            email,
+            secret: hash_password(password)?,
+            privilege,
+            content_authority: None,
+            google_auth: None,
+        })
+    }
+
+    pub fn verify_password(&self, password: &str) -> bool {
+        match PasswordHash::new(&self.secret) {
+            Ok(hash) => Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok(),
+            Err(_) => false,
+        }
+    }
+}
+
+#[async_trait]
+pub trait FindAccount {
+    /// Matches either the username or the email, case insensitively.
+    async fn find(&self, login: &str) -> Result<Option<Account>, Error>;
+}
+
+#[async_trait]
+impl FindAccount for AccountStore {
+    async fn find(&self, login: &str) -> Result<Option<Account>, Error> {
+        Ok(self.values().await?.into_iter().find(|a| {
+            a.username.eq_ignore_ascii_case(login) || a.email.eq_ignore_ascii_case(login)
+        }))
+    }
+}
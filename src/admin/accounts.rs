@@ -0,0 +1,70 @@
+use super::api::{ApiError, ApiResult};
+use super::auth::{end_sessions, AccountView, SessionStore};
+use super::privilege::{Authorized, ServerConfig};
+use crate::account::{Account, AccountStore, ContentAuthority, FindAccount, Privilege};
+use crate::db::Store;
+use rocket::http::Status;
+use rocket::response::status::Created;
+use rocket::serde::json::Json;
+use rocket::{delete, get, post, State};
+use serde::Deserialize;
+use ulid::Ulid;
+
+#[derive(Deserialize, Debug)]
+pub struct AccountInput {
+    pub username: String,
+    pub email: String,
+    pub password: String,
+    pub privilege: Privilege,
+    pub content_authority: Option<ContentAuthority>,
+}
+
+#[get("/accounts")]
+pub async fn list(
+    _auth: Authorized<ServerConfig>,
+    accounts: &State<AccountStore>,
+) -> ApiResult<Json<Vec<AccountView>>> {
+    Ok(Json(accounts.values().await?.iter().map(AccountView::from).collect()))
+}
+
+#[post("/accounts", data = "<input>")]
+pub async fn create(
+    _auth: Authorized<ServerConfig>,
+    input: Json<AccountInput>,
+    accounts: &State<AccountStore>,
+) -> ApiResult<Created<Json<AccountView>>> {
+    let input = input.into_inner();
+    if input.username.trim().is_empty() || input.password.is_empty() {
+        return Err(ApiError::unprocessable("Username and password are required"));
+    }
+    for login in [&input.username, &input.email] {
+        if accounts.find(login).await?.is_some() {
+            return Err(ApiError::new(Status::Conflict, format!("{} is already taken", login)));
+        }
+    }
+    let mut account = Account::new(input.username, input.email, &input.password, input.privilege)?;
+    account.content_authority = input.content_authority;
+    accounts.insert(account.id, account.clone()).await?;
+    Ok(Created::new(format!("/api/accounts/{}", account.id)).body(Json(AccountView::from(&account))))
+}
+
+#[delete("/accounts/<id>")]
+pub async fn delete(
+    auth: Authorized<ServerConfig>,
+    id: &str,
+    accounts: &State<AccountStore>,
+    sessions: &State<SessionStore>,
+) -> ApiResult<Status> {
+    let id = Ulid::from_string(id).map_err(|e| ApiError::bad_request(format!("Invalid id {}: {}", id, e)))?;
+    if id == auth.account.id {
+        return Err(ApiError::unprocessable("An account can not delete itself"));
+    }
+    match accounts.get(&id).await? {
+        Some(_) => {
+            accounts.delete(&id).await?;
+            end_sessions(sessions, id).await?;
+            Ok(Status::NoContent)
+        }
+        None => Err(ApiError::not_found("Account not found")),
+    }
+}
@@ -0,0 +1,82 @@
+use crate::account::Privilege;
+use crate::Error;
+use rocket::http::Status;
+use rocket::response::{self, Responder};
+use rocket::serde::json::Json;
+use rocket::{catch, Request};
+use serde::Serialize;
+
+pub type ApiResult<T> = Result<T, ApiError>;
+
+/// JSON error body returned by every admin API route.
+#[derive(Debug, Serialize)]
+pub struct ApiError {
+    #[serde(skip)]
+    pub status: Status,
+    pub error: String,
+    /// Privilege flag that was missing when access is denied.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub privilege: Option<Privilege>,
+}
+
+impl ApiError {
+    pub fn new(status: Status, error: impl ToString) -> Self {
+        ApiError {
+            status,
+            error: error.to_string(),
+            required: None,
+            privilege: None,
+        }
+    }
+
+    pub fn forbidden(required: &'static str, privilege: Privilege) -> Self {
+        ApiError {
+            required: Some(required),
+            privilege: Some(privilege),
+            ..Self::new(Status::Forbidden, format!("Missing {} privilege", required))
+        }
+    }
+
+    pub fn not_found(error: impl ToString) -> Self {
+        Self::new(Status::NotFound, error)
+    }
+
+    pub fn bad_request(error: impl ToString) -> Self {
+        Self::new(Status::BadRequest, error)
+    }
+
+    pub fn unprocessable(error: impl ToString) -> Self {
+        Self::new(Status::UnprocessableEntity, error)
+    }
+}
+
+impl From<Error> for ApiError {
+    fn from(e: Error) -> Self {
+        Self::new(Status::InternalServerError, e)
+    }
+}
+
+impl<'r> Responder<'r, 'static> for ApiError {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let status = self.status;
+        (status, Json(self)).respond_to(request)
+    }
+}
+
+/// Turns guard and body parsing failures under `/api` into JSON errors.
+#[catch(default)]
+pub fn catcher(status: Status, request: &Request) -> ApiError {
+    if let Some(denied) = request.local_cache(|| None::<Denied>) {
+        return ApiError::forbidden(denied.required, denied.privilege);
+    }
+    ApiError::new(status, status.reason().unwrap_or("Unknown error"))
+}
+
+/// Left in the request cache by a failing privilege guard for [`catcher`].
+#[derive(Clone, Copy, Debug)]
+pub struct Denied {
+    pub required: &'static str,
+    pub privilege: Privilege,
+}
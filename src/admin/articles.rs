@@ -0,0 +1,285 @@
+use super::api::{ApiError, ApiResult};
+use super::privilege::{authorize_article, authorize_new, Authorized, ManageContent};
+use crate::post::article::{Article, Content};
+use crate::post::{ArticleRepository, MarkdownType};
+use crate::render::{purge_article, PageCache};
+use crate::Slugify;
+use chrono::{DateTime, Utc};
+use rocket::http::Status;
+use rocket::response::status::Created;
+use rocket::serde::json::Json;
+use rocket::{delete, get, post, put, State};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use ulid::Ulid;
+
+fn default_section() -> String {
+    "blog".to_string()
+}
+
+fn default_draft() -> bool {
+    true
+}
+
+/// Request body of create and update, new articles start as drafts.
+#[derive(Deserialize, Debug)]
+pub struct ArticleInput {
+    #[serde(default = "default_section")]
+    pub section: String,
+    pub slug: Option<String>,
+    pub title: String,
+    #[serde(default)]
+    pub author: String,
+    pub date: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub categories: Vec<String>,
+    pub summary: Option<String>,
+    #[serde(default = "default_draft")]
+    pub draft: bool,
+    #[serde(default)]
+    pub extra: Map<String, Value>,
+    pub content: Content,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ArticleSummary {
+    pub id: Ulid,
+    pub slug: String,
+    pub section: String,
+    pub url: String,
+    pub title: String,
+    pub author: String,
+    pub timestamp: DateTime<Utc>,
+    pub tags: Vec<String>,
+    pub categories: Vec<String>,
+    pub draft: bool,
+}
+
+impl From<&Article> for ArticleSummary {
+    fn from(article: &Article) -> Self {
+        ArticleSummary {
+            id: article.id(),
+            slug: article.slug.clone(),
+            section: article.section.clone(),
+            url: article.url(),
+            title: article.title.clone(),
+            author: article.author.clone(),
+            timestamp: article.timestamp,
+            tags: article.tags.clone(),
+            categories: article.categories.clone(),
+            draft: article.draft,
+        }
+    }
+}
+
+fn parse_id(id: &str) -> ApiResult<Ulid> {
+    Ulid::from_string(id).map_err(|e| ApiError::bad_request(format!("Invalid id {}: {}", id, e)))
+}
+
+/// HTML must parse and is sanitized, Markdown must render to valid HTML.
+fn validate_content(content: Content) -> ApiResult<Content> {
+    match content {
+        Content::Html(html) => match html.validate() {
+            Ok(()) => Ok(Content::Html(html.sanitize())),
+            Err(e) => Err(ApiError::unprocessable(format!("Invalid HTML: {}", e))),
+        },
+        Content::Markdown(markdown) => {
+            match markdown.to_html(MarkdownType::Gfm).and_then(|html| html.validate()) {
+                Ok(()) => Ok(Content::Markdown(markdown)),
+                Err(e) => Err(ApiError::unprocessable(format!("Invalid Markdown: {}", e))),
+            }
+        }
+    }
+}
+
+impl ArticleInput {
+    /// `timestamp` is used when the input carries no date.
+    fn into_article(self, id: Ulid, timestamp: DateTime<Utc>) -> ApiResult<Article> {
+        let slug = self.slug.unwrap_or_else(|| self.title.slugify());
+        if !slug.is_slug() || !self.section.is_slug() {
+            return Err(ApiError::unprocessable(
+                "Section and slug may only contain letters, digits, '-' and '_'",
+            ));
+        }
+        if self.title.trim().is_empty() {
+            return Err(ApiError::unprocessable("Title is required"));
+        }
+        let mut article = Article::new(
+            id,
+            slug,
+            self.section,
+            self.title,
+            self.author,
+            self.date.unwrap_or(timestamp),
+            validate_content(self.content)?,
+        );
+        article.summary = self.summary;
+        article.tags = self.tags;
+        article.categories = self.categories;
+        article.draft = self.draft;
+        article.extra = self.extra;
+        Ok(article)
+    }
+}
+
+async fn save(
+    articles: &ArticleRepository,
+    page_cache: &PageCache,
+    article: Article,
+    previous: Option<&Article>,
+) -> ApiResult<Article> {
+    if let Some(other) = articles.by_slug(&article.section, &article.slug).await? {
+        if other.id() != article.id() {
+            return Err(ApiError::new(
+                Status::Conflict,
+                format!("{} is already used by another article", article.url()),
+            ));
+        }
+    }
+    articles.save(article.clone()).await?;
+    purge_article(page_cache, previous, Some(&article)).await?;
+    Ok(article)
+}
+
+#[get("/articles?<section>")]
+pub async fn list(
+    auth: Authorized<ManageContent>,
+    section: Option<&str>,
+    articles: &State<ArticleRepository>,
+) -> ApiResult<Json<Vec<ArticleSummary>>> {
+    let list = match section {
+        Some(section) => articles.list(section).await?,
+        None => articles.all().await?,
+    };
+    Ok(Json(
+        list.iter()
+            .filter(|a| authorize_article(&auth.account, a).is_ok())
+            .map(ArticleSummary::from)
+            .collect(),
+    ))
+}
+
+async fn find(articles: &ArticleRepository, auth: &Authorized<ManageContent>, id: &str) -> ApiResult<Article> {
+    let article = articles
+        .get(&parse_id(id)?)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Article not found"))?;
+    authorize_article(&auth.account, &article)?;
+    Ok(article)
+}
+
+#[get("/articles/<id>")]
+pub async fn get(
+    auth: Authorized<ManageContent>,
+    id: &str,
+    articles: &State<ArticleRepository>,
+) -> ApiResult<Json<Article>> {
+    find(articles, &auth, id).await.map(Json)
+}
+
+#[post("/articles", data = "<input>")]
+pub async fn create(
+    auth: Authorized<ManageContent>,
+    input: Json<ArticleInput>,
+    articles: &State<ArticleRepository>,
+    page_cache: &State<PageCache>,
+) -> ApiResult<Created<Json<Article>>> {
+    authorize_new(&auth.account)?;
+    let mut article = input.into_inner().into_article(Ulid::new(), Utc::now())?;
+    article.owner = Some(auth.account.id);
+    let article = save(articles, page_cache, article, None).await?;
+    Ok(Created::new(format!("/api/articles/{}", article.id())).body(Json(article)))
+}
+
+#[put("/articles/<id>", data = "<input>")]
+pub async fn update(
+    auth: Authorized<ManageContent>,
+    id: &str,
+    input: Json<ArticleInput>,
+    articles: &State<ArticleRepository>,
+    page_cache: &State<PageCache>,
+) -> ApiResult<Json<Article>> {
+    let previous = find(articles, &auth, id).await?;
+    let mut article = input.into_inner().into_article(previous.id(), previous.timestamp)?;
+    article.owner = previous.owner;
+    save(articles, page_cache, article, Some(&previous)).await.map(Json)
+}
+
+#[delete("/articles/<id>")]
+pub async fn delete(
+    auth: Authorized<ManageContent>,
+    id: &str,
+    articles: &State<ArticleRepository>,
+    page_cache: &State<PageCache>,
+) -> ApiResult<Status> {
+    let article = find(articles, &auth, id).await?;
+    articles.remove(&article.id()).await?;
+    purge_article(page_cache, Some(&article), None).await?;
+    Ok(Status::NoContent)
+}
+
+async fn set_draft(
+    auth: &Authorized<ManageContent>,
+    id: &str,
+    draft: bool,
+    articles: &ArticleRepository,
+    page_cache: &PageCache,
+) -> ApiResult<Json<Article>> {
+    let previous = find(articles, auth, id).await?;
+    let mut article = previous.clone();
+    article.draft = draft;
+    save(articles, page_cache, article, Some(&previous)).await.map(Json)
+}
+
+#[post("/articles/<id>/publish")]
+pub async fn publish(
+    auth: Authorized<ManageContent>,
+    id: &str,
+    articles: &State<ArticleRepository>,
+    page_cache: &State<PageCache>,
+) -> ApiResult<Json<Article>> {
+    set_draft(&auth, id, false, articles, page_cache).await
+}
+
+#[post("/articles/<id>/unpublish")]
+pub async fn unpublish(
+    auth: Authorized<ManageContent>,
+    id: &str,
+    articles: &State<ArticleRepository>,
+    page_cache: &State<PageCache>,
+) -> ApiResult<Json<Article>> {
+    set_draft(&auth, id, true, articles, page_cache).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::post::{Html, Markdown};
+
+    fn rejection(content: Content) -> String {
+        let error = validate_content(content).unwrap_err();
+        assert_eq!(error.status, Status::UnprocessableEntity);
+        error.error
+    }
+
+    #[test]
+    fn keeps_valid_content() {
+        let markdown = Content::Markdown(Markdown::new("# Title\n\nBody".to_string()));
+        assert!(matches!(validate_content(markdown), Ok(Content::Markdown(_))));
+        let html = Content::Html(Html::new("<p>Body<script>alert(1)</script></p>".to_string()));
+        match validate_content(html) {
+            Ok(Content::Html(html)) => assert_eq!(html.to_string(), "<p>Body</p>"),
+            _ => panic!("valid HTML rejected"),
+        }
+    }
+
+    #[test]
+    fn reports_why_content_is_rejected() {
+        let markdown = rejection(Content::Markdown(Markdown::new("  \n".to_string())));
+        assert_eq!(markdown, "Invalid Markdown: No HTML element in \"\"");
+        let html = rejection(Content::Html(Html::new("plain text".to_string())));
+        assert_eq!(html, "Invalid HTML: No HTML element in \"plain text\"");
+    }
+}
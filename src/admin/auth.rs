@@ -0,0 +1,325 @@
+use super::api::{ApiError, ApiResult};
+use crate::account::{Account, AccountStore, ContentAuthority, FindAccount, GetPrivileges, Privilege};
+use crate::config::Config;
+use crate::db::{FileData, Store};
+use crate::Error;
+use chrono::{DateTime, Duration, Utc};
+use rocket::http::{Cookie, CookieJar, SameSite, Status};
+use rocket::request::{FromRequest, Outcome};
+use rocket::serde::json::Json;
+use rocket::{get, post, Request, State};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tokio::io::AsyncWriteExt;
+use ulid::Ulid;
+
+pub const SESSION_COOKIE: &str = "session";
+
+/// Sessions by the id kept in the private session cookie, so logging out or
+/// deleting an account ends them on the server too.
+pub type SessionStore = FileData<Ulid, Session>;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Session {
+    pub account: Ulid,
+    pub expires: DateTime<Utc>,
+}
+
+/// Account fields that are safe to send to the admin frontend.
+#[derive(Serialize, Debug)]
+pub struct AccountView {
+    pub id: Ulid,
+    pub username: String,
+    pub email: String,
+    pub privilege: Privilege,
+    pub content_authority: ContentAuthority,
+    pub google_linked: bool,
+}
+
+impl From<&Account> for AccountView {
+    fn from(account: &Account) -> Self {
+        AccountView {
+            id: account.id,
+            username: account.username.clone(),
+            email: account.email.clone(),
+            privilege: account.privilege,
+            content_authority: account.get_privileges().content_authority,
+            google_linked: account.google_auth.is_some(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Login {
+    /// Username or email.
+    pub login: String,
+    pub password: String,
+}
+
+/// Stores a new session of `account` and its id in the private session
+/// cookie.
+pub async fn start_session(
+    cookies: &CookieJar<'_>,
+    account: &Account,
+    config: &Config,
+    sessions: &SessionStore,
+) -> Result<(), Error> {
+    let id = Ulid::new();
+    let session = Session {
+        account: account.id,
+        expires: Utc::now() + Duration::hours(config.session_hours),
+    };
+    sessions.insert(id, session).await?;
+    let cookie = Cookie::build((SESSION_COOKIE, id.to_string()))
+        .http_only(true)
+        .secure(!cfg!(debug_assertions))
+        .same_site(SameSite::Lax)
+        .path("/");
+    cookies.add_private(cookie);
+    Ok(())
+}
+
+/// Drops the sessions that expired, the guard refuses them anyway.
+pub async fn end_expired_sessions(sessions: &SessionStore) -> Result<(), Error> {
+    let now = Utc::now();
+    for id in sessions.keys().await? {
+        if sessions.get(&id).await?.is_some_and(|s| s.expires <= now) {
+            sessions.delete(&id).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Ends the expired sessions now and then every hour.
+pub fn prune_sessions(sessions: &SessionStore) {
+    let sessions = sessions.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(60 * 60));
+        loop {
+            interval.tick().await;
+            if let Err(e) = end_expired_sessions(&sessions).await {
+                rocket::warn!("Failed to end expired sessions: {}", e);
+            }
+        }
+    });
+}
+
+/// Ends every session of the account `id`.
+pub async fn end_sessions(sessions: &SessionStore, id: Ulid) -> Result<(), Error> {
+    for session in sessions.keys().await? {
+        if sessions.get(&session).await?.is_some_and(|s| s.account == id) {
+            sessions.delete(&session).await?;
+        }
+    }
+    Ok(())
+}
+
+fn session_id(cookies: &CookieJar<'_>) -> Option<Ulid> {
+    Ulid::from_string(cookies.get_private(SESSION_COOKIE)?.value()).ok()
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Account {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let Some(id) = session_id(request.cookies()) else {
+            return Outcome::Error((Status::Unauthorized, ()));
+        };
+        let (Some(accounts), Some(sessions)) = (
+            request.rocket().state::<AccountStore>(),
+            request.rocket().state::<SessionStore>(),
+        ) else {
+            return Outcome::Error((Status::InternalServerError, ()));
+        };
+        let session = match sessions.get(&id).await {
+            Ok(Some(session)) if session.expires > Utc::now() => session,
+            Ok(_) => return Outcome::Error((Status::Unauthorized, ())),
+            Err(_) => return Outcome::Error((Status::InternalServerError, ())),
+        };
+        match accounts.get(&session.account).await {
+            Ok(Some(account)) => Outcome::Success(account),
+            Ok(None) => Outcome::Error((Status::Unauthorized, ())),
+            Err(_) => Outcome::Error((Status::InternalServerError, ())),
+        }
+    }
+}
+
+/// File below `db_dir` a generated admin password is written to.
+pub const ADMIN_PASSWORD_FILE: &str = "admin_password";
+
+/// Creates the initial Admin account when the store is empty.
+///
+/// Without `admin_password` its password is generated and written to
+/// [`ADMIN_PASSWORD_FILE`], readable by the owner only, rather than to the
+/// log.
+pub async fn bootstrap(accounts: &AccountStore, config: &Config) -> Result<(), Error> {
+    if !accounts.is_empty().await? {
+        return Ok(());
+    }
+    let password = match &config.admin_password {
+        Some(password) => password.to_string(),
+        None => {
+            let password = crate::account::generate_password();
+            let path = config.db_dir.join(ADMIN_PASSWORD_FILE);
+            write_secret(&path, &password).await?;
+            println!(
+                "Created admin account \"{}\", its password is in {}",
+                config.admin_username,
+                path.display()
+            );
+            password
+        }
+    };
+    let account = Account::new(
+        config.admin_username.to_string(),
+        config.admin_email.to_string(),
+        &password,
+        Privilege::Admin,
+    )?;
+    accounts.insert(account.id, account).await
+}
+
+async fn write_secret(path: &Path, secret: &str) -> Result<(), Error> {
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(path).await?;
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600)).await?;
+    file.write_all(secret.as_bytes()).await?;
+    file.sync_all().await?;
+    Ok(())
+}
+
+#[post("/login", data = "<login>")]
+pub async fn login(
+    login: Json<Login>,
+    cookies: &CookieJar<'_>,
+    accounts: &State<AccountStore>,
+    sessions: &State<SessionStore>,
+    config: &State<Config>,
+) -> ApiResult<Json<AccountView>> {
+    let account = accounts.find(&login.login).await?;
+    match account {
+        Some(account) if account.verify_password(&login.password) => {
+            start_session(cookies, &account, config, sessions).await?;
+            Ok(Json(AccountView::from(&account)))
+        }
+        _ => Err(ApiError::new(Status::Unauthorized, "Invalid login or password")),
+    }
+}
+
+#[post("/logout")]
+pub async fn logout(cookies: &CookieJar<'_>, sessions: &State<SessionStore>) -> ApiResult<Status> {
+    if let Some(id) = session_id(cookies) {
+        sessions.delete(&id).await?;
+    }
+    cookies.remove_private(Cookie::build(SESSION_COOKIE).path("/"));
+    Ok(Status::NoContent)
+}
+
+#[get("/me")]
+pub async fn me(account: Account) -> Json<AccountView> {
+    Json(AccountView::from(&account))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{client, login, PASSWORD};
+    use super::*;
+    use crate::db::Db;
+
+    async fn me(client: &rocket::local::asynchronous::Client) -> Status {
+        client.get("/api/me").dispatch().await.status()
+    }
+
+    #[tokio::test]
+    async fn logs_in_with_the_username_or_the_email() {
+        let client = client(Config::default()).await;
+        assert_eq!(me(&client).await, Status::Unauthorized);
+        assert_eq!(login(&client, "admin", "wrong").await, Status::Unauthorized);
+        assert_eq!(login(&client, "nobody", PASSWORD).await, Status::Unauthorized);
+        assert_eq!(me(&client).await, Status::Unauthorized);
+
+        assert_eq!(login(&client, "Admin", PASSWORD).await, Status::Ok);
+        let response = client.get("/api/me").dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let account: serde_json::Value = response.into_json().await.unwrap();
+        assert_eq!(account["username"], "admin");
+        assert!(account.get("secret").is_none());
+
+        assert_eq!(login(&client, "admin@localhost", PASSWORD).await, Status::Ok);
+        assert_eq!(me(&client).await, Status::Ok);
+    }
+
+    #[tokio::test]
+    async fn revokes_the_session_on_logout() {
+        let client = client(Config::default()).await;
+        assert_eq!(login(&client, "admin", PASSWORD).await, Status::Ok);
+        let cookie = client.cookies().get_private(SESSION_COOKIE).unwrap();
+        assert!(cookie.http_only().unwrap_or(false));
+
+        let response = client.post("/api/logout").dispatch().await;
+        assert_eq!(response.status(), Status::NoContent);
+        assert_eq!(me(&client).await, Status::Unauthorized);
+        // A copy of the cookie is worthless once the session ended.
+        let replayed = client.get("/api/me").private_cookie(cookie).dispatch().await;
+        assert_eq!(replayed.status(), Status::Unauthorized);
+    }
+
+    #[tokio::test]
+    async fn refuses_an_expired_session() {
+        let client = client(Config { session_hours: 0, ..Config::default() }).await;
+        assert_eq!(login(&client, "admin", PASSWORD).await, Status::Ok);
+        assert_eq!(me(&client).await, Status::Unauthorized);
+    }
+
+    fn db() -> Db {
+        Db::new(std::env::temp_dir().join(format!("myweb-auth-{}", Ulid::new())).into())
+    }
+
+    #[tokio::test]
+    async fn ends_only_the_expired_sessions() {
+        let sessions: SessionStore = db().open("sessions").await.unwrap();
+        let session = |hours| Session {
+            account: Ulid::new(),
+            expires: Utc::now() + Duration::hours(hours),
+        };
+        let (expired, live) = (Ulid::new(), Ulid::new());
+        sessions.insert(expired, session(-1)).await.unwrap();
+        sessions.insert(live, session(1)).await.unwrap();
+        end_expired_sessions(&sessions).await.unwrap();
+        assert_eq!(sessions.keys().await.unwrap(), [live]);
+    }
+
+    #[tokio::test]
+    async fn writes_a_generated_password_to_a_private_file() {
+        let db_dir: std::sync::Arc<Path> =
+            std::env::temp_dir().join(format!("myweb-auth-{}", Ulid::new())).into();
+        let accounts: AccountStore = Db::new(db_dir.clone()).open("accounts").await.unwrap();
+        let config = Config { db_dir: db_dir.clone(), ..Config::default() };
+        bootstrap(&accounts, &config).await.unwrap();
+
+        let path = db_dir.join(ADMIN_PASSWORD_FILE);
+        let password = tokio::fs::read_to_string(&path).await.unwrap();
+        let admin = accounts.find("admin").await.unwrap().unwrap();
+        assert!(admin.verify_password(&password));
+        assert_eq!(admin.privilege, Privilege::Admin);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = tokio::fs::metadata(&path).await.unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        // A second start leaves the account and the file alone.
+        bootstrap(&accounts, &config).await.unwrap();
+        assert_eq!(accounts.len().await.unwrap(), 1);
+        assert_eq!(tokio::fs::read_to_string(&path).await.unwrap(), password);
+    }
+}
@@ -0,0 +1,32 @@
+use super::api::ApiResult;
+use super::privilege::{Authorized, SiteConfig};
+use crate::cache::CacheStats;
+use crate::render::{Dependency, PageCache};
+use rocket::serde::json::Json;
+use rocket::{get, post, State};
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct Invalidated {
+    pub purged: usize,
+}
+
+#[get("/cache")]
+pub async fn stats(
+    _auth: Authorized<SiteConfig>,
+    page_cache: &State<PageCache>,
+) -> ApiResult<Json<CacheStats>> {
+    Ok(Json(page_cache.stats().await))
+}
+
+/// Drops the cached pages depending on the listed resources, for changes made
+/// outside the admin API, like `[{"type": "article", "id": "01J..."}]`.
+#[post("/cache/invalidate", data = "<input>")]
+pub async fn invalidate(
+    _auth: Authorized<SiteConfig>,
+    input: Json<Vec<Dependency>>,
+    page_cache: &State<PageCache>,
+) -> ApiResult<Json<Invalidated>> {
+    let purged = page_cache.invalidate(&input.into_inner()).await;
+    Ok(Json(Invalidated { purged }))
+}
@@ -1,19 +1,91 @@
+mod accounts;
+mod api;
+mod articles;
+mod auth;
+mod cache;
+mod oauth;
+mod pages;
+mod privilege;
+mod settings;
+mod themes;
+
 use std::path::{Path, PathBuf};
 use figment::Figment;
-use rocket::{Build, fs::NamedFile, get, response::{status::NotFound, Redirect}, routes};
+use rocket::{Build, catchers, fs::NamedFile, get, response::{status::NotFound, Redirect}, routes};
 use rocket::http::Status;
+use crate::account::AccountStore;
 use crate::config::Config;
+use crate::db::Db;
+use crate::post::ArticleRepository;
+use crate::render::PageCache;
+use crate::page::PageStore;
+use crate::settings::SettingsStore;
+use crate::theme::ThemeRegistry;
 
-pub async fn launch(figment: &Figment) -> Result<rocket::Rocket<Build>, rocket::Error> {
-    let port = &figment
+pub async fn launch(
+    figment: &Figment,
+    db: &Db,
+    articles: &ArticleRepository,
+    page_cache: &PageCache,
+    themes: &ThemeRegistry,
+) -> crate::Result<rocket::Rocket<Build>> {
+    let config = figment
         .extract::<Config>()
-        .expect("Failed to extract config")
-        .admin_port;
+        .expect("Failed to extract config");
+    let port = &config.admin_port;
 
     let figment = figment.clone().merge(("port", port));
 
+    let accounts: AccountStore = db.open("accounts").await?;
+    auth::bootstrap(&accounts, &config).await?;
+    let sessions: auth::SessionStore = db.open("sessions").await?;
+    auth::prune_sessions(&sessions);
+    let settings: SettingsStore = db.open("settings").await?;
+    let pages: PageStore = db.open("pages").await?;
+
     let rocket =
-        rocket::custom(figment).mount("/", routes![admin_index, admin_assets, admin_page]);
+        rocket::custom(figment)
+            .manage(db.clone())
+            .manage(articles.clone())
+            .manage(page_cache.clone())
+            .manage(accounts)
+            .manage(sessions)
+            .manage(settings)
+            .manage(pages)
+            .manage(themes.clone())
+            .attach(rocket::fairing::AdHoc::config::<Config>())
+            .mount("/", routes![admin_index, admin_assets, admin_page])
+            .mount(
+                "/api",
+                routes![
+                    auth::login,
+                    auth::logout,
+                    auth::me,
+                    oauth::login,
+                    oauth::callback,
+                    accounts::list,
+                    accounts::create,
+                    accounts::delete,
+                    articles::list,
+                    articles::get,
+                    articles::create,
+                    articles::update,
+                    articles::delete,
+                    articles::publish,
+                    articles::unpublish,
+                    settings::get,
+                    settings::update,
+                    pages::list,
+                    pages::get,
+                    pages::update,
+                    pages::delete,
+                    themes::list,
+                    themes::activate,
+                    cache::stats,
+                    cache::invalidate
+                ],
+            )
+            .register("/api", catchers![api::catcher]);
 
     Ok(rocket)
 }
@@ -40,4 +112,44 @@ pub async fn admin_index() -> Result<NamedFile, status::Custom<&'static str>> {
         .map_err(|_| status::Custom(Status::InternalServerError, "Failed to open html file"))
 }
 
-use rocket::response::status;
\ No newline at end of file
+use rocket::response::status;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use figment::providers::Serialized;
+    use rocket::local::asynchronous::Client;
+    use serde_json::json;
+    use ulid::Ulid;
+
+    pub(super) const PASSWORD: &str = "admin password";
+
+    /// An admin server on a fresh db and articles directory, the password of
+    /// its admin account being [`PASSWORD`].
+    pub(super) async fn client(config: Config) -> Client {
+        let dir = std::env::temp_dir().join(format!("myweb-admin-{}", Ulid::new()));
+        let config = Config {
+            theme_dir: Path::new(env!("CARGO_MANIFEST_DIR")).join("theme").into(),
+            db_dir: dir.join("db").into(),
+            articles_dir: dir.join("articles").into(),
+            admin_password: Some(PASSWORD.into()),
+            ..config
+        };
+        let figment = Figment::from(rocket::Config::debug_default())
+            .merge(Serialized::defaults(&config));
+        let db = Db::new(config.db_dir.clone());
+        let articles = ArticleRepository::index(config.articles_dir.clone()).await.unwrap();
+        let page_cache = PageCache::new(&config.cache);
+        let themes = ThemeRegistry::open(&config, &db).await.unwrap();
+        let rocket = launch(&figment, &db, &articles, &page_cache, &themes).await.unwrap();
+        Client::tracked(rocket).await.unwrap()
+    }
+
+    pub(super) async fn login(client: &Client, login: &str, password: &str) -> Status {
+        client
+            .post("/api/login")
+            .json(&json!({ "login": login, "password": password }))
+            .dispatch()
+            .await
+            .status()
+    }
+}
@@ -0,0 +1,543 @@
+use super::api::{ApiError, ApiResult};
+use super::auth::{start_session, SessionStore};
+use crate::account::{generate_password, Account, AccountStore, FindAccount, Privilege};
+use crate::config::Config;
+use crate::db::Store;
+use crate::json::Json;
+use crate::string::Slugify;
+use crate::Error;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::Utc;
+use rocket::http::{Cookie, CookieJar, SameSite, Status};
+use rocket::response::Redirect;
+use ring::signature::{RsaPublicKeyComponents, RSA_PKCS1_2048_8192_SHA256};
+use rocket::{get, State};
+use serde::Deserialize;
+use serde_json::Value;
+use ulid::Ulid;
+
+const OAUTH_COOKIE: &str = "oauth";
+
+/// The subset of the OpenID provider metadata the flow needs.
+#[derive(Deserialize, Debug)]
+struct Discovery {
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+/// The provider's signing keys, a JSON Web Key Set.
+#[derive(Deserialize, Debug)]
+struct Jwks {
+    keys: Vec<Jwk>,
+}
+
+/// An RSA public key of the set, other key types have no `n` and `e`.
+#[derive(Deserialize, Debug)]
+struct Jwk {
+    kty: String,
+    kid: Option<String>,
+    #[serde(default)]
+    n: String,
+    #[serde(default)]
+    e: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct TokenHeader {
+    alg: String,
+    kid: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct TokenResponse {
+    id_token: String,
+}
+
+struct Client<'a> {
+    id: &'a str,
+    secret: &'a str,
+    issuer: &'a str,
+    redirect_url: &'a str,
+}
+
+impl<'a> Client<'a> {
+    fn from_config(config: &'a Config) -> ApiResult<Client<'a>> {
+        match (&config.google_client_id, &config.google_client_secret) {
+            (Some(id), Some(secret)) => Ok(Client {
+                id,
+                secret,
+                issuer: config.google_issuer.trim_end_matches('/'),
+                redirect_url: &config.google_redirect_url,
+            }),
+            _ => Err(ApiError::not_found("Google sign-in is not configured")),
+        }
+    }
+
+    async fn discover(&self) -> Result<Discovery, Error> {
+        Ok(reqwest::get(format!("{}/.well-known/openid-configuration", self.issuer))
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+
+    /// Checks the RS256 signature of `id_token` with the key of the
+    /// provider's set it names and returns its decoded claims.
+    async fn verify(&self, discovery: &Discovery, id_token: &str) -> Result<Value, Error> {
+        let decode = |part: &str| {
+            URL_SAFE_NO_PAD
+                .decode(part)
+                .map_err(|e| Error::from(format!("Malformed ID token: {}", e)))
+        };
+        let parts: Vec<&str> = id_token.split('.').collect();
+        let [encoded_header, payload, signature] = parts[..] else {
+            return Err("Malformed ID token".to_string().into());
+        };
+        let header: TokenHeader = serde_json::from_slice(&decode(encoded_header)?)?;
+        if header.alg != "RS256" {
+            return Err(format!("Unexpected token algorithm {}", header.alg).into());
+        }
+        let jwks: Jwks = reqwest::get(&discovery.jwks_uri)
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let key = jwks
+            .keys
+            .iter()
+            .filter(|k| k.kty == "RSA")
+            .find(|k| header.kid.is_none() || k.kid == header.kid)
+            .ok_or("No key of the provider matches the token".to_string())?;
+        let key = RsaPublicKeyComponents {
+            n: decode(&key.n)?,
+            e: decode(&key.e)?,
+        };
+        let signed = &id_token[..encoded_header.len() + 1 + payload.len()];
+        key.verify(&RSA_PKCS1_2048_8192_SHA256, signed.as_bytes(), &decode(signature)?)
+            .map_err(|_| "Invalid token signature".to_string())?;
+        Ok(serde_json::from_slice(&decode(payload)?)?)
+    }
+
+    /// Exchanges the authorization code for the ID token claims.
+    ///
+    /// The token's signature is verified against the provider's keys before
+    /// its issuer, audience, expiry and nonce are checked.
+    async fn claims(&self, code: &str, nonce: &str) -> Result<Value, Error> {
+        let discovery = self.discover().await?;
+        let token: TokenResponse = reqwest::Client::new()
+            .post(&discovery.token_endpoint)
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", self.redirect_url),
+                ("client_id", self.id),
+                ("client_secret", self.secret),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let claims = self.verify(&discovery, &token.id_token).await?;
+
+        let issuer = claims["iss"].as_str().unwrap_or_default().trim_end_matches('/');
+        // Google issues both forms.
+        if issuer != self.issuer && format!("https://{}", issuer) != self.issuer {
+            return Err(format!("Unexpected token issuer {}", issuer).into());
+        }
+        let audience = match &claims["aud"] {
+            Value::String(aud) => aud == self.id,
+            Value::Array(aud) => aud.iter().any(|a| a.as_str() == Some(self.id)),
+            _ => false,
+        };
+        if !audience {
+            return Err("Token was issued for another client".to_string().into());
+        }
+        if claims["exp"].as_i64().unwrap_or_default() < Utc::now().timestamp() {
+            return Err("Token expired".to_string().into());
+        }
+        if claims["nonce"].as_str() != Some(nonce) {
+            return Err("Token nonce mismatch".to_string().into());
+        }
+        if claims["sub"].as_str().unwrap_or_default().is_empty() {
+            return Err("Token has no subject".to_string().into());
+        }
+        Ok(claims)
+    }
+}
+
+/// Finds the account linked to the Google subject, links the account with
+/// exactly the same verified email, or creates a new Reader account.
+///
+/// Unlike a login, a username equal to the email does not count, or an
+/// address could take over the account that merely chose it as a name.
+async fn link_account(accounts: &AccountStore, claims: Value) -> Result<Account, Error> {
+    let sub = claims["sub"].as_str().unwrap_or_default();
+    let email = claims["email"].as_str().unwrap_or_default().to_string();
+    let verified = claims["email_verified"].as_bool().unwrap_or(false);
+
+    let linked = accounts.values().await?.into_iter().find(|a| {
+        a.google_auth
+            .as_ref()
+            .is_some_and(|g| Value::from(g.clone())["sub"].as_str() == Some(sub))
+    });
+    let mut account = match linked {
+        Some(account) => account,
+        None => match accounts
+            .values()
+            .await?
+            .into_iter()
+            .find(|a| !email.is_empty() && a.email == email)
+        {
+            Some(account) if verified => account,
+            Some(_) => {
+                return Err(format!("{} belongs to an account but is not verified", email).into())
+            }
+            None => {
+                let base = match email.split('@').next() {
+                    Some(name) if !name.is_empty() => name.slugify(),
+                    _ => format!("google_{}", sub),
+                };
+                let mut username = base.clone();
+                let mut n = 1;
+                while accounts.find(&username).await?.is_some() {
+                    n += 1;
+                    username = format!("{}{}", base, n);
+                }
+                Account::new(username, email, &generate_password(), Privilege::Reader)?
+            }
+        },
+    };
+    account.google_auth = Some(Json::new(claims));
+    accounts.insert(account.id, account.clone()).await?;
+    Ok(account)
+}
+
+#[get("/oauth/google/login")]
+pub async fn login(cookies: &CookieJar<'_>, config: &State<Config>) -> ApiResult<Redirect> {
+    let client = Client::from_config(config)?;
+    let discovery = client
+        .discover()
+        .await
+        .map_err(|e| ApiError::new(Status::BadGateway, e))?;
+    let state = Ulid::new().to_string();
+    let nonce = generate_password();
+    cookies.add_private(
+        Cookie::build((OAUTH_COOKIE, format!("{}:{}", state, nonce)))
+            .http_only(true)
+            .secure(!cfg!(debug_assertions))
+            .same_site(SameSite::Lax)
+            .max_age(rocket::time::Duration::minutes(10))
+            .path("/"),
+    );
+    let url = reqwest::Url::parse_with_params(
+        &discovery.authorization_endpoint,
+        &[
+            ("response_type", "code"),
+            ("client_id", client.id),
+            ("redirect_uri", client.redirect_url),
+            ("scope", "openid email profile"),
+            ("state", &state),
+            ("nonce", &nonce),
+        ],
+    )
+    .map_err(|e| ApiError::new(Status::BadGateway, e))?;
+    Ok(Redirect::to(url.to_string()))
+}
+
+#[get("/oauth/google/callback?<code>&<state>&<error>")]
+pub async fn callback(
+    code: Option<&str>,
+    state: Option<&str>,
+    error: Option<&str>,
+    cookies: &CookieJar<'_>,
+    accounts: &State<AccountStore>,
+    sessions: &State<SessionStore>,
+    config: &State<Config>,
+) -> ApiResult<Redirect> {
+    let client = Client::from_config(config)?;
+    if let Some(error) = error {
+        return Err(ApiError::new(Status::Unauthorized, format!("Google sign-in failed: {}", error)));
+    }
+    let pending = cookies.get_private(OAUTH_COOKIE);
+    cookies.remove_private(Cookie::build(OAUTH_COOKIE).path("/"));
+    let (expected_state, nonce) = pending
+        .as_ref()
+        .and_then(|c| c.value().split_once(':'))
+        .ok_or_else(|| ApiError::bad_request("No sign-in in progress"))?;
+    if state != Some(expected_state) {
+        return Err(ApiError::bad_request("State mismatch"));
+    }
+    let code = code.ok_or_else(|| ApiError::bad_request("Missing authorization code"))?;
+    let claims = client
+        .claims(code, nonce)
+        .await
+        .map_err(|e| ApiError::new(Status::Unauthorized, e))?;
+    let account = link_account(accounts, claims)
+        .await
+        .map_err(|e| ApiError::new(Status::Unauthorized, e))?;
+    start_session(cookies, &account, config, sessions).await?;
+    Ok(Redirect::to("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::FileData;
+    use serde_json::json;
+    use std::path::PathBuf;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use ring::rand::SystemRandom;
+    use ring::signature::{RsaKeyPair, RSA_PKCS1_SHA256};
+    use tokio::net::{TcpListener, TcpStream};
+
+    const CODE: &str = "the-code";
+
+    /// Reads one request, returning its request line and body.
+    async fn read_request(stream: &mut TcpStream) -> (String, String) {
+        let mut request = Vec::new();
+        let mut buffer = [0; 1024];
+        loop {
+            let n = stream.read(&mut buffer).await.unwrap();
+            request.extend_from_slice(&buffer[..n]);
+            let text = String::from_utf8_lossy(&request).to_string();
+            let Some((head, body)) = text.split_once("\r\n\r\n") else {
+                continue;
+            };
+            let length = head
+                .lines()
+                .filter_map(|l| l.split_once(':'))
+                .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+                .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+                .unwrap_or(0);
+            if body.len() >= length || n == 0 {
+                let line = head.lines().next().unwrap_or_default().to_string();
+                return (line, body.to_string());
+            }
+        }
+    }
+
+    fn key_pair() -> RsaKeyPair {
+        RsaKeyPair::from_der(include_bytes!("testdata/oidc_key.der")).unwrap()
+    }
+
+    /// `claims` under `header`, signed with the provider's key.
+    fn signed(header: Value, claims: &Value) -> String {
+        let key_pair = key_pair();
+        let signed = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header.to_string()),
+            URL_SAFE_NO_PAD.encode(claims.to_string())
+        );
+        let mut signature = vec![0; key_pair.public().modulus_len()];
+        key_pair
+            .sign(&RSA_PKCS1_SHA256, &SystemRandom::new(), signed.as_bytes(), &mut signature)
+            .unwrap();
+        format!("{}.{}", signed, URL_SAFE_NO_PAD.encode(signature))
+    }
+
+    fn rs256(claims: Value) -> String {
+        signed(json!({ "alg": "RS256", "kid": "key" }), &claims)
+    }
+
+    /// Starts a local OpenID provider answering discovery, key set and token
+    /// requests, its ID token made by `id_token` from `claims` with the
+    /// provider as issuer. Returns the issuer url.
+    async fn mock_issuer(mut claims: Value, id_token: fn(Value) -> String) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+        claims["iss"] = json!(issuer);
+        let id_token = id_token(claims);
+        let key: RsaPublicKeyComponents<Vec<u8>> = key_pair().public().into();
+        let jwks = json!({ "keys": [
+            { "kty": "EC", "kid": "other", "crv": "P-256", "x": "", "y": "" },
+            { "kty": "RSA", "kid": "key", "alg": "RS256", "use": "sig",
+              "n": URL_SAFE_NO_PAD.encode(&key.n), "e": URL_SAFE_NO_PAD.encode(&key.e) },
+        ]});
+        let base = issuer.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let (line, body) = read_request(&mut stream).await;
+                let (status, response) = if line.starts_with("GET /.well-known/openid-configuration ") {
+                    let discovery = json!({
+                        "authorization_endpoint": format!("{}/authorize", base),
+                        "token_endpoint": format!("{}/token", base),
+                        "jwks_uri": format!("{}/jwks", base),
+                    });
+                    ("200 OK", discovery)
+                } else if line.starts_with("GET /jwks ") {
+                    ("200 OK", jwks.clone())
+                } else if line.starts_with("POST /token ")
+                    && body.contains("grant_type=authorization_code")
+                    && body.contains(&format!("code={}", CODE))
+                    && body.contains("client_id=client")
+                {
+                    ("200 OK", json!({ "id_token": id_token }))
+                } else {
+                    ("400 Bad Request", json!({ "error": "invalid_request" }))
+                };
+                let response = response.to_string();
+                let reply = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    response.len(),
+                    response
+                );
+                let _ = stream.write_all(reply.as_bytes()).await;
+            }
+        });
+        issuer
+    }
+
+    fn claims(nonce: &str) -> Value {
+        json!({
+            "sub": "1234",
+            "aud": "client",
+            "exp": Utc::now().timestamp() + 600,
+            "nonce": nonce,
+            "email": "writer@example.com",
+            "email_verified": true,
+        })
+    }
+
+    fn client(issuer: &str) -> Client<'_> {
+        Client {
+            id: "client",
+            secret: "secret",
+            issuer,
+            redirect_url: "http://localhost:8001/api/oauth/google/callback",
+        }
+    }
+
+    async fn accounts() -> (AccountStore, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("myweb-oauth-{}", Ulid::new()));
+        (FileData::open(&dir, "accounts").await.unwrap(), dir)
+    }
+
+    async fn writer(accounts: &AccountStore) -> Account {
+        let writer = Account::new(
+            "writer".to_string(),
+            "writer@example.com".to_string(),
+            "password",
+            Privilege::Writer,
+        )
+        .unwrap();
+        accounts.insert(writer.id, writer.clone()).await.unwrap();
+        writer
+    }
+
+    #[tokio::test]
+    async fn discovers_and_exchanges_the_code() {
+        let issuer = mock_issuer(claims("nonce"), rs256).await;
+        let client = client(&issuer);
+        let discovery = client.discover().await.unwrap();
+        assert_eq!(discovery.token_endpoint, format!("{}/token", issuer));
+
+        let claims = client.claims(CODE, "nonce").await.unwrap();
+        assert_eq!(claims["sub"], "1234");
+        assert!(client.claims("another-code", "nonce").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn rejects_another_nonce() {
+        let issuer = mock_issuer(claims("nonce"), rs256).await;
+        let error = client(&issuer).claims(CODE, "replayed").await.unwrap_err();
+        assert!(error.to_string().contains("nonce"));
+    }
+
+    #[tokio::test]
+    async fn links_the_account_with_the_verified_email() {
+        let (accounts, dir) = accounts().await;
+        let writer = writer(&accounts).await;
+
+        let linked = link_account(&accounts, claims("nonce")).await.unwrap();
+        assert_eq!(linked.id, writer.id);
+        assert_eq!(linked.privilege, Privilege::Writer);
+        assert!(accounts.get(&writer.id).await.unwrap().unwrap().google_auth.is_some());
+
+        // Signing in again finds it by subject.
+        let again = link_account(&accounts, claims("other")).await.unwrap();
+        assert_eq!(again.id, writer.id);
+        assert_eq!(accounts.len().await.unwrap(), 1);
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn creates_a_reader_account() {
+        let (accounts, dir) = accounts().await;
+        let account = link_account(&accounts, claims("nonce")).await.unwrap();
+        assert_eq!(account.username, "writer");
+        assert_eq!(account.privilege, Privilege::Reader);
+        assert_eq!(accounts.len().await.unwrap(), 1);
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn refuses_an_unverified_email_of_another_account() {
+        let (accounts, dir) = accounts().await;
+        writer(&accounts).await;
+
+        let mut unverified = claims("nonce");
+        unverified["email_verified"] = json!(false);
+        assert!(link_account(&accounts, unverified).await.is_err());
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn rejects_a_token_with_an_invalid_signature() {
+        let tampered = |claims: Value| {
+            let token = rs256(claims.clone());
+            let mut forged = claims;
+            forged["sub"] = json!("admin");
+            let (header, rest) = token.split_once('.').unwrap();
+            let signature = rest.split_once('.').unwrap().1;
+            format!("{}.{}.{}", header, URL_SAFE_NO_PAD.encode(forged.to_string()), signature)
+        };
+        let issuer = mock_issuer(claims("nonce"), tampered).await;
+        let error = client(&issuer).claims(CODE, "nonce").await.unwrap_err();
+        assert_eq!(error.to_string(), "Invalid token signature");
+    }
+
+    #[tokio::test]
+    async fn rejects_an_unsigned_token() {
+        let unsigned = |claims: Value| {
+            format!(
+                "{}.{}.",
+                URL_SAFE_NO_PAD.encode(r#"{"alg":"none"}"#),
+                URL_SAFE_NO_PAD.encode(claims.to_string())
+            )
+        };
+        let issuer = mock_issuer(claims("nonce"), unsigned).await;
+        let error = client(&issuer).claims(CODE, "nonce").await.unwrap_err();
+        assert_eq!(error.to_string(), "Unexpected token algorithm none");
+    }
+
+    #[tokio::test]
+    async fn rejects_a_token_signed_with_an_unknown_key() {
+        let unknown = |claims: Value| signed(json!({ "alg": "RS256", "kid": "rotated" }), &claims);
+        let issuer = mock_issuer(claims("nonce"), unknown).await;
+        let error = client(&issuer).claims(CODE, "nonce").await.unwrap_err();
+        assert_eq!(error.to_string(), "No key of the provider matches the token");
+    }
+
+    #[tokio::test]
+    async fn links_only_the_exact_email() {
+        let (accounts, dir) = accounts().await;
+        let named = Account::new(
+            "writer@example.com".to_string(),
+            "someone@localhost".to_string(),
+            "password",
+            Privilege::Admin,
+        )
+        .unwrap();
+        accounts.insert(named.id, named.clone()).await.unwrap();
+
+        let account = link_account(&accounts, claims("nonce")).await.unwrap();
+        assert_ne!(account.id, named.id);
+        assert_eq!(account.privilege, Privilege::Reader);
+        assert!(accounts.get(&named.id).await.unwrap().unwrap().google_auth.is_none());
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
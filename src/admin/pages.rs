@@ -0,0 +1,86 @@
+use super::api::{ApiError, ApiResult};
+use super::privilege::{Authorized, SiteConfig};
+use crate::config::Config;
+use crate::db::Store;
+use crate::page::{FindPage, PageDef, PageStore};
+use crate::post::ArticleRepository;
+use crate::render::{Dependency, PageCache};
+use crate::theme::ActiveTheme;
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::{delete, get, put, State};
+use std::path::PathBuf;
+
+fn page_slug(path: PathBuf) -> String {
+    path.to_string_lossy().to_string()
+}
+
+#[get("/pages")]
+pub async fn list(
+    _auth: Authorized<SiteConfig>,
+    pages: &State<PageStore>,
+    articles: &State<ArticleRepository>,
+    config: &State<Config>,
+) -> ApiResult<Json<Vec<PageDef>>> {
+    Ok(Json(pages.pages(articles, &config.pages).await?))
+}
+
+#[get("/pages/<slug..>")]
+pub async fn get(
+    _auth: Authorized<SiteConfig>,
+    slug: PathBuf,
+    pages: &State<PageStore>,
+    articles: &State<ArticleRepository>,
+    config: &State<Config>,
+) -> ApiResult<Json<PageDef>> {
+    let slug = page_slug(slug);
+    match pages.page(&slug, articles, &config.pages).await? {
+        Some(page) => Ok(Json(page)),
+        None => Err(ApiError::not_found(format!("No page {}", slug))),
+    }
+}
+
+/// Stores the page under `slug`, replacing a page article or configured page
+/// of the same slug.
+#[put("/pages/<slug..>", data = "<input>")]
+pub async fn update(
+    _auth: Authorized<SiteConfig>,
+    slug: PathBuf,
+    input: Json<PageDef>,
+    pages: &State<PageStore>,
+    theme: ActiveTheme,
+    page_cache: &State<PageCache>,
+) -> ApiResult<Json<PageDef>> {
+    let mut page = input.into_inner();
+    page.slug = page_slug(slug);
+    if !page.has_valid_slug() {
+        return Err(ApiError::unprocessable(
+            "Slug segments may only contain letters, digits, '-' and '_'",
+        ));
+    }
+    theme
+        .manifest
+        .resolve(&page.template, &page.overrides())
+        .map_err(ApiError::unprocessable)?;
+    pages.insert(page.slug.clone(), page.clone()).await?;
+    page_cache.invalidate(&[Dependency::Page(page.slug.clone())]).await;
+    Ok(Json(page))
+}
+
+/// Removes the stored page, a page article or configured page of the same slug
+/// is served again.
+#[delete("/pages/<slug..>")]
+pub async fn delete(
+    _auth: Authorized<SiteConfig>,
+    slug: PathBuf,
+    pages: &State<PageStore>,
+    page_cache: &State<PageCache>,
+) -> ApiResult<Status> {
+    let slug = page_slug(slug);
+    if pages.get(&slug).await?.is_none() {
+        return Err(ApiError::not_found(format!("No stored page {}", slug)));
+    }
+    pages.delete(&slug).await?;
+    page_cache.invalidate(&[Dependency::Page(slug)]).await;
+    Ok(Status::NoContent)
+}
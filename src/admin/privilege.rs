@@ -0,0 +1,247 @@
+use super::api::{ApiError, ApiResult, Denied};
+use crate::account::{Account, ContentAuthority, GetPrivileges, Privileges};
+use crate::post::article::Article;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome};
+use rocket::Request;
+use std::marker::PhantomData;
+
+/// A flag of [`Privileges`] a route can require through [`Authorized`].
+pub trait Permission: Send + Sync + 'static {
+    const NAME: &'static str;
+    fn granted(privileges: &Privileges) -> bool;
+}
+
+pub struct ServerConfig;
+pub struct SiteConfig;
+/// Any content authority other than [`ContentAuthority::None`], the article
+/// itself is checked with [`authorize_article`].
+pub struct ManageContent;
+
+impl Permission for ServerConfig {
+    const NAME: &'static str = "server_config";
+    fn granted(privileges: &Privileges) -> bool {
+        privileges.server_config
+    }
+}
+
+impl Permission for SiteConfig {
+    const NAME: &'static str = "site_config";
+    fn granted(privileges: &Privileges) -> bool {
+        privileges.site_config
+    }
+}
+
+impl Permission for ManageContent {
+    const NAME: &'static str = "content_authority";
+    fn granted(privileges: &Privileges) -> bool {
+        privileges.content_authority != ContentAuthority::None
+    }
+}
+
+/// The logged in [`Account`], only if it holds permission `P`.
+pub struct Authorized<P: Permission> {
+    pub account: Account,
+    _permission: PhantomData<P>,
+}
+
+#[rocket::async_trait]
+impl<'r, P: Permission> FromRequest<'r> for Authorized<P> {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let account = match request.guard::<Account>().await {
+            Outcome::Success(account) => account,
+            Outcome::Error(e) => return Outcome::Error(e),
+            Outcome::Forward(s) => return Outcome::Forward(s),
+        };
+        if P::granted(&account.get_privileges()) {
+            return Outcome::Success(Authorized {
+                account,
+                _permission: PhantomData,
+            });
+        }
+        request.local_cache(|| {
+            Some(Denied {
+                required: P::NAME,
+                privilege: account.privilege,
+            })
+        });
+        Outcome::Error((Status::Forbidden, ()))
+    }
+}
+
+/// Checks the account's content authority against `article`'s id and owner.
+pub fn authorize_article(account: &Account, article: &Article) -> ApiResult<()> {
+    let authority = account.get_privileges().content_authority;
+    match authority.allows(account, Some(article.id()), article.owner) {
+        true => Ok(()),
+        false => Err(ApiError::forbidden(ManageContent::NAME, account.privilege)),
+    }
+}
+
+/// Checks that the account may create content owned by itself.
+pub fn authorize_new(account: &Account) -> ApiResult<()> {
+    let authority = account.get_privileges().content_authority;
+    match authority.allows(account, None, Some(account.id)) {
+        true => Ok(()),
+        false => Err(ApiError::forbidden(ManageContent::NAME, account.privilege)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{client, login, PASSWORD};
+    use super::*;
+    use crate::account::Privilege;
+    use crate::config::Config;
+    use crate::post::article::Content;
+    use crate::post::Markdown;
+    use chrono::Utc;
+    use rocket::local::asynchronous::Client;
+    use serde_json::{json, Value};
+    use ulid::Ulid;
+
+    fn account(privilege: Privilege, content_authority: Option<ContentAuthority>) -> Account {
+        Account {
+            id: Ulid::new(),
+            username: "account".to_string(),
+            email: "account@localhost".to_string(),
+            secret: String::new(),
+            privilege,
+            content_authority,
+            google_auth: None,
+        }
+    }
+
+    fn article(owner: Option<Ulid>) -> Article {
+        let mut article = Article::new(
+            Ulid::new(),
+            "article".to_string(),
+            "blog".to_string(),
+            "Article".to_string(),
+            String::new(),
+            Utc::now(),
+            Content::Markdown(Markdown::new("Body".to_string())),
+        );
+        article.owner = owner;
+        article
+    }
+
+    #[test]
+    fn grants_the_content_authority_of_the_privilege() {
+        let other = article(Some(Ulid::new()));
+        for privilege in [Privilege::Admin, Privilege::Moderator] {
+            assert!(authorize_article(&account(privilege, None), &other).is_ok());
+        }
+        let writer = account(Privilege::Writer, None);
+        assert!(authorize_article(&writer, &article(Some(writer.id))).is_ok());
+        assert!(authorize_article(&writer, &other).is_err());
+        assert!(authorize_article(&writer, &article(None)).is_err());
+        assert!(authorize_new(&writer).is_ok());
+
+        let reader = account(Privilege::Reader, None);
+        assert!(authorize_article(&reader, &article(Some(reader.id))).is_err());
+        let error = authorize_new(&reader).unwrap_err();
+        assert_eq!(error.status, Status::Forbidden);
+        assert_eq!(error.required, Some("content_authority"));
+    }
+
+    #[test]
+    fn lists_articles_and_owners_in_except_and_specific() {
+        let (listed, by_listed_owner, other) = (article(None), article(Some(Ulid::new())), article(None));
+        let ids = vec![listed.id(), by_listed_owner.owner.unwrap()];
+
+        let except = account(Privilege::Writer, Some(ContentAuthority::Except(ids.clone())));
+        assert!(authorize_article(&except, &listed).is_err());
+        assert!(authorize_article(&except, &by_listed_owner).is_err());
+        assert!(authorize_article(&except, &other).is_ok());
+        assert!(authorize_new(&except).is_ok());
+
+        let specific = account(Privilege::Writer, Some(ContentAuthority::Specific(ids)));
+        assert!(authorize_article(&specific, &listed).is_ok());
+        assert!(authorize_article(&specific, &by_listed_owner).is_ok());
+        assert!(authorize_article(&specific, &other).is_err());
+        assert!(authorize_new(&specific).is_err());
+
+        let oneself = account(Privilege::Admin, Some(ContentAuthority::Oneself));
+        assert!(authorize_article(&oneself, &other).is_err());
+        assert!(authorize_article(&oneself, &article(Some(oneself.id))).is_ok());
+    }
+
+    async fn create_account(client: &Client, username: &str, privilege: &str) {
+        let account = json!({
+            "username": username,
+            "email": format!("{}@localhost", username),
+            "password": PASSWORD,
+            "privilege": privilege,
+        });
+        let response = client.post("/api/accounts").json(&account).dispatch().await;
+        assert_eq!(response.status(), Status::Created);
+    }
+
+    async fn get(client: &Client, uri: &str) -> (Status, Value) {
+        let response = client.get(uri.to_string()).dispatch().await;
+        (response.status(), response.into_json().await.unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn refuses_routes_the_privilege_lacks() {
+        let client = client(Config::default()).await;
+        assert_eq!(login(&client, "admin", PASSWORD).await, Status::Ok);
+        create_account(&client, "moderator", "Moderator").await;
+        create_account(&client, "reader", "Reader").await;
+        assert_eq!(get(&client, "/api/accounts").await.0, Status::Ok);
+
+        assert_eq!(login(&client, "moderator", PASSWORD).await, Status::Ok);
+        assert_eq!(get(&client, "/api/settings").await.0, Status::Ok);
+        assert_eq!(get(&client, "/api/articles").await.0, Status::Ok);
+        let (status, denied) = get(&client, "/api/accounts").await;
+        assert_eq!(status, Status::Forbidden);
+        assert_eq!(denied["required"], "server_config");
+        assert_eq!(denied["privilege"], "Moderator");
+
+        assert_eq!(login(&client, "reader", PASSWORD).await, Status::Ok);
+        let (status, denied) = get(&client, "/api/settings").await;
+        assert_eq!((status, &denied["required"]), (Status::Forbidden, &json!("site_config")));
+        let (status, denied) = get(&client, "/api/articles").await;
+        assert_eq!((status, &denied["required"]), (Status::Forbidden, &json!("content_authority")));
+    }
+
+    #[tokio::test]
+    async fn lets_writers_manage_their_own_articles_only() {
+        let client = client(Config::default()).await;
+        let input = |title: &str| json!({ "title": title, "content": { "markdown": "Body" } });
+        let create = |title: &'static str| {
+            let client = &client;
+            async move {
+                let response = client.post("/api/articles").json(&input(title)).dispatch().await;
+                assert_eq!(response.status(), Status::Created);
+                let article: Value = response.into_json().await.unwrap();
+                article["id"].as_str().unwrap().to_string()
+            }
+        };
+        assert_eq!(login(&client, "admin", PASSWORD).await, Status::Ok);
+        create_account(&client, "writer", "Writer").await;
+        let admins = create("By the admin").await;
+
+        assert_eq!(login(&client, "writer", PASSWORD).await, Status::Ok);
+        let own = create("By the writer").await;
+        let (_, listed) = get(&client, "/api/articles").await;
+        let ids: Vec<&str> = listed.as_array().unwrap().iter().map(|a| a["id"].as_str().unwrap()).collect();
+        assert_eq!(ids, [own.as_str()]);
+
+        assert_eq!(get(&client, &format!("/api/articles/{}", own)).await.0, Status::Ok);
+        assert_eq!(get(&client, &format!("/api/articles/{}", admins)).await.0, Status::Forbidden);
+        let update = client
+            .put(format!("/api/articles/{}", admins))
+            .json(&input("Taken over"))
+            .dispatch()
+            .await;
+        assert_eq!(update.status(), Status::Forbidden);
+        let delete = client.delete(format!("/api/articles/{}", admins)).dispatch().await;
+        assert_eq!(delete.status(), Status::Forbidden);
+        let publish = client.post(format!("/api/articles/{}/publish", own)).dispatch().await;
+        assert_eq!(publish.status(), Status::Ok);
+    }
+}
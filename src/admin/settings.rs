@@ -0,0 +1,38 @@
+use super::api::{ApiError, ApiResult};
+use super::privilege::{Authorized, SiteConfig};
+use crate::config::Config;
+use crate::render::{Dependency, PageCache};
+use crate::settings::{GetSettings, SettingsStore, SiteSettings};
+use rocket::serde::json::Json;
+use rocket::{get, put, State};
+
+#[get("/settings")]
+pub async fn get(
+    _auth: Authorized<SiteConfig>,
+    settings: &State<SettingsStore>,
+    config: &State<Config>,
+) -> ApiResult<Json<SiteSettings>> {
+    Ok(Json(settings.site(&config.site).await?))
+}
+
+/// Replaces the site settings and drops every cached page since they all
+/// render them.
+#[put("/settings", data = "<input>")]
+pub async fn update(
+    _auth: Authorized<SiteConfig>,
+    input: Json<SiteSettings>,
+    settings: &State<SettingsStore>,
+    page_cache: &State<PageCache>,
+) -> ApiResult<Json<SiteSettings>> {
+    let mut input = input.into_inner();
+    if input.site_name.trim().is_empty() {
+        return Err(ApiError::unprocessable("Site name is required"));
+    }
+    input.base_url = input.base_url.trim_end_matches('/').to_string();
+    if !input.base_url.starts_with("http://") && !input.base_url.starts_with("https://") {
+        return Err(ApiError::unprocessable("Base url must be an absolute http(s) url"));
+    }
+    settings.save_site(input.clone()).await?;
+    page_cache.invalidate(&[Dependency::Settings, Dependency::Menus]).await;
+    Ok(Json(input))
+}
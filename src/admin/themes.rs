@@ -0,0 +1,40 @@
+use super::api::{ApiError, ApiResult};
+use super::privilege::{Authorized, SiteConfig};
+use crate::render::PageCache;
+use crate::theme::{InstalledTheme, ThemeRegistry};
+use rocket::serde::json::Json;
+use rocket::serde::Deserialize;
+use rocket::{get, put, State};
+
+#[derive(Deserialize)]
+pub struct ThemeChoice {
+    pub name: String,
+}
+
+#[get("/themes")]
+pub async fn list(
+    _auth: Authorized<SiteConfig>,
+    themes: &State<ThemeRegistry>,
+) -> ApiResult<Json<Vec<InstalledTheme>>> {
+    Ok(Json(themes.installed().await?))
+}
+
+/// Switches the theme and drops every cached page rendered with the old one.
+#[put("/themes/active", data = "<input>")]
+pub async fn activate(
+    _auth: Authorized<SiteConfig>,
+    input: Json<ThemeChoice>,
+    themes: &State<ThemeRegistry>,
+    page_cache: &State<PageCache>,
+) -> ApiResult<Json<InstalledTheme>> {
+    let theme = themes
+        .activate(&input.name)
+        .await
+        .map_err(ApiError::unprocessable)?;
+    page_cache.clear().await;
+    Ok(Json(InstalledTheme {
+        name: theme.name.to_string(),
+        active: true,
+        manifest: theme.manifest.as_ref().clone(),
+    }))
+}
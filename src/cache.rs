@@ -0,0 +1,652 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use lru::LruCache;
+use rocket::http::{Header, Status};
+use rocket::request::Request;
+use rocket::response::{self, content::RawHtml, Responder, Response};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use ulid::Ulid;
+use crate::page::PAGES_SECTION;
+use crate::post::article::Article;
+use crate::post::taxonomy::{term_slug, Taxonomy};
+use crate::{Error, StableHash};
+
+/// The `[default.cache]` table of the config.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    /// Most pages kept, the least recently used one makes room for a new one.
+    /// `0` disables the cache.
+    pub capacity: usize,
+    /// Seconds a rendered page is served from the cache.
+    pub ttl: u64,
+    /// Seconds by route, one of [`ROUTES`], instead of `ttl`.
+    pub routes: BTreeMap<String, u64>,
+    /// `Cache-Control` header of the rendered pages.
+    pub cache_control: String,
+    /// `Cache-Control` by route, instead of `cache_control`.
+    pub cache_control_routes: BTreeMap<String, String>,
+}
+
+/// Routes the cache settings can be set for.
+pub const ROUTES: [&str; 8] = [
+    "index",
+    "blog_index",
+    "blog",
+    "tags",
+    "categories",
+    "projects",
+    "project",
+    "pages",
+];
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            capacity: 1000,
+            ttl: 3600,
+            routes: BTreeMap::new(),
+            cache_control: "public, no-cache".to_string(),
+            cache_control_routes: BTreeMap::new(),
+        }
+    }
+}
+
+impl CacheConfig {
+    /// Fails on a route that is not one of [`ROUTES`], most likely a typo.
+    pub fn validate(&self) -> Result<(), Error> {
+        let unknown: Vec<&str> = self
+            .routes
+            .keys()
+            .chain(self.cache_control_routes.keys())
+            .map(String::as_str)
+            .filter(|route| !ROUTES.contains(route))
+            .collect();
+        match unknown.is_empty() {
+            true => Ok(()),
+            false => Err(format!(
+                "Unknown cache routes {}, expected one of {}",
+                unknown.join(", "),
+                ROUTES.join(", ")
+            )
+            .into()),
+        }
+    }
+}
+
+/// Counters since the start or the last [`PageCache::clear`], for the admin
+/// API.
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub capacity: usize,
+    pub hits: u64,
+    pub misses: u64,
+    /// Pages found older than their TTL, counted as misses too.
+    pub expired: u64,
+    /// Pages dropped to stay within the capacity.
+    pub evictions: u64,
+    /// Pages dropped because a resource they depend on changed.
+    pub invalidations: u64,
+    /// Resources at least one cached page depends on.
+    pub dependencies: usize,
+}
+
+/// A resource pages are rendered from, see [`PageCache::invalidate`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "type", content = "id", rename_all = "snake_case")]
+pub enum Dependency {
+    /// An article by id, the page shows it or a preview of it.
+    Article(Ulid),
+    /// Which articles a section publishes and in what order.
+    Section(String),
+    /// Which articles are filed under a term, by its slug.
+    Term(Taxonomy, String),
+    /// The terms of a taxonomy and their counts, like the tag cloud.
+    Terms(Taxonomy),
+    /// A template of the active theme by name.
+    Template(String),
+    /// The definition of the page at a slug.
+    Page(String),
+    /// The menus, from the settings and the page articles.
+    Menus,
+    Settings,
+}
+
+/// The resources one page depends on, collected while the route renders it.
+#[derive(Clone, Debug)]
+pub struct Dependencies {
+    resources: HashSet<Dependency>,
+    /// Timestamp of the newest article added.
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+impl Default for Dependencies {
+    /// What every page shows, the settings, the menus and the tag cloud.
+    fn default() -> Self {
+        Dependencies {
+            resources: HashSet::from([
+                Dependency::Settings,
+                Dependency::Menus,
+                Dependency::Terms(Taxonomy::Tags),
+            ]),
+            last_modified: None,
+        }
+    }
+}
+
+impl Dependencies {
+    pub fn with(mut self, dependency: Dependency) -> Self {
+        self.resources.insert(dependency);
+        self
+    }
+
+    pub fn article(mut self, article: &Article) -> Self {
+        self.last_modified = self.last_modified.max(Some(article.timestamp));
+        self.with(Dependency::Article(article.id()))
+    }
+
+    pub fn articles<'a>(self, articles: impl IntoIterator<Item = &'a Article>) -> Self {
+        articles.into_iter().fold(self, Dependencies::article)
+    }
+
+    pub fn extend(mut self, dependencies: impl IntoIterator<Item = Dependency>) -> Self {
+        self.resources.extend(dependencies);
+        self
+    }
+
+    /// Resources of the pages to drop after an article changed, `previous`
+    /// and `current` being its versions before and after, `None` for an
+    /// article created or deleted.
+    pub fn changed(previous: Option<&Article>, current: Option<&Article>) -> Vec<Dependency> {
+        let versions: Vec<&Article> = previous.into_iter().chain(current).collect();
+        let mut changed: Vec<Dependency> = versions
+            .iter()
+            .map(|a| Dependency::Article(a.id()))
+            .collect();
+        let listed = |a: &Article| (a.section.clone(), a.slug.clone(), a.timestamp, a.draft);
+        let relisted = match (previous, current) {
+            (Some(previous), Some(current)) => listed(previous) != listed(current),
+            _ => true,
+        };
+        for taxonomy in [Taxonomy::Tags, Taxonomy::Categories] {
+            let refiled = relisted
+                || previous.map(|a| taxonomy.terms(a)) != current.map(|a| taxonomy.terms(a));
+            if !refiled {
+                continue;
+            }
+            changed.push(Dependency::Terms(taxonomy));
+            for article in &versions {
+                for term in taxonomy.terms(article) {
+                    changed.push(Dependency::Term(taxonomy, term_slug(term)));
+                }
+            }
+        }
+        for article in &versions {
+            if relisted {
+                changed.push(Dependency::Section(article.section.clone()));
+            }
+            if article.section == PAGES_SECTION {
+                changed.push(Dependency::Menus);
+            }
+        }
+        changed
+    }
+}
+
+/// A rendered page and the validators of its responses.
+#[derive(Clone, Debug)]
+pub struct CachedPage {
+    pub html: Arc<str>,
+    /// Quoted hash of `html`.
+    pub etag: Arc<str>,
+    /// Timestamp of the newest article the page shows, if it shows any.
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+impl CachedPage {
+    pub fn new(html: Arc<str>, last_modified: Option<DateTime<Utc>>) -> Self {
+        CachedPage {
+            etag: format!("\"{:016x}\"", html.stable_hash()).into(),
+            html,
+            last_modified,
+        }
+    }
+}
+
+/// A page response with its `ETag`, `Last-Modified` and `Cache-Control`
+/// headers, `304 Not Modified` when the client's copy is still current.
+pub struct Page {
+    page: CachedPage,
+    cache_control: String,
+}
+
+struct Entry {
+    page: CachedPage,
+    created: Instant,
+    dependencies: HashSet<Dependency>,
+}
+
+struct Pages {
+    pages: LruCache<String, Entry>,
+    /// Ids of the cached pages by the resources they depend on.
+    dependents: HashMap<Dependency, HashSet<String>>,
+    /// Generation of the theme the cached pages were rendered with.
+    generation: u64,
+    stats: CacheStats,
+}
+
+impl Pages {
+    /// Whether pages of theme `generation` may be served and cached, a newer
+    /// generation drops the pages of the older one first.
+    fn current(&mut self, generation: u64) -> bool {
+        if generation > self.generation {
+            self.pages.clear();
+            self.dependents.clear();
+            self.generation = generation;
+        }
+        generation == self.generation
+    }
+
+    fn remove(&mut self, id: &str) -> bool {
+        match self.pages.pop(id) {
+            Some(entry) => {
+                self.unlink(id, &entry);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn unlink(&mut self, id: &str, entry: &Entry) {
+        for dependency in &entry.dependencies {
+            if let Some(ids) = self.dependents.get_mut(dependency) {
+                ids.remove(id);
+                if ids.is_empty() {
+                    self.dependents.remove(dependency);
+                }
+            }
+        }
+    }
+}
+
+/// Rendered pages by cache id, bounded to the configured capacity.
+#[derive(Clone)]
+pub struct PageCache {
+    pages: Arc<Mutex<Pages>>,
+    config: Arc<CacheConfig>,
+}
+
+impl PageCache {
+    pub fn new(config: &CacheConfig) -> Self {
+        // A disabled cache keeps an empty slot, `insert` stores nothing.
+        let capacity = NonZeroUsize::new(config.capacity).unwrap_or(NonZeroUsize::MIN);
+        PageCache {
+            pages: Arc::new(Mutex::new(Pages {
+                pages: LruCache::new(capacity),
+                dependents: HashMap::new(),
+                generation: 0,
+                stats: CacheStats::default(),
+            })),
+            config: Arc::new(config.clone()),
+        }
+    }
+
+    /// TTL of `route`, the default one unless the config sets another.
+    pub fn ttl(&self, route: &str) -> Duration {
+        Duration::from_secs(*self.config.routes.get(route).unwrap_or(&self.config.ttl))
+    }
+
+    /// `Cache-Control` of `route`, the default one unless the config sets
+    /// another.
+    pub fn cache_control(&self, route: &str) -> &str {
+        self.config
+            .cache_control_routes
+            .get(route)
+            .unwrap_or(&self.config.cache_control)
+    }
+
+    /// Wraps `page` for a response of `route`.
+    pub fn respond(&self, page: CachedPage, route: &str) -> Page {
+        Page {
+            page,
+            cache_control: self.cache_control(route).to_string(),
+        }
+    }
+
+    /// The page cached under `id` if it is younger than `ttl`, an older one is
+    /// dropped so the caller renders it again. Nothing is served to a request
+    /// still holding a theme older than the cached pages' `generation`.
+    pub async fn get(&self, id: &str, ttl: Duration, generation: u64) -> Option<CachedPage> {
+        let mut pages = self.pages.lock().await;
+        if !pages.current(generation) {
+            pages.stats.misses += 1;
+            return None;
+        }
+        let cached = pages
+            .pages
+            .get(id)
+            .map(|entry| (entry.page.clone(), entry.created.elapsed() < ttl));
+        match cached {
+            Some((page, true)) => {
+                pages.stats.hits += 1;
+                Some(page)
+            }
+            Some((_, false)) => {
+                pages.remove(id);
+                pages.stats.expired += 1;
+                pages.stats.misses += 1;
+                None
+            }
+            None => {
+                pages.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Caches `page` under `id` until it expires, is evicted or one of its
+    /// `dependencies` is invalidated. A page rendered with a theme older than
+    /// `generation` of the cached pages is not kept.
+    pub async fn insert(
+        &self,
+        id: String,
+        page: CachedPage,
+        dependencies: &Dependencies,
+        generation: u64,
+    ) {
+        if self.config.capacity == 0 {
+            return;
+        }
+        let mut pages = self.pages.lock().await;
+        if !pages.current(generation) {
+            return;
+        }
+        pages.remove(&id);
+        for dependency in &dependencies.resources {
+            pages.dependents.entry(dependency.clone()).or_default().insert(id.clone());
+        }
+        let entry = Entry {
+            page,
+            created: Instant::now(),
+            dependencies: dependencies.resources.clone(),
+        };
+        if let Some((evicted, old)) = pages.pages.push(id, entry) {
+            pages.unlink(&evicted, &old);
+            pages.stats.evictions += 1;
+        }
+    }
+
+    /// Drops every page depending on one of `changed`, returns how many.
+    pub async fn invalidate(&self, changed: &[Dependency]) -> usize {
+        let mut pages = self.pages.lock().await;
+        let ids: HashSet<String> = changed
+            .iter()
+            .filter_map(|dependency| pages.dependents.get(dependency))
+            .flatten()
+            .cloned()
+            .collect();
+        let purged = ids.into_iter().filter(|id| pages.remove(id)).count();
+        pages.stats.invalidations += purged as u64;
+        purged
+    }
+
+    /// Drops every page, for changes all of them depend on like a new theme,
+    /// and starts the counters over.
+    pub async fn clear(&self) {
+        let mut pages = self.pages.lock().await;
+        pages.pages.clear();
+        pages.dependents.clear();
+        pages.stats = CacheStats::default();
+    }
+
+    pub async fn stats(&self) -> CacheStats {
+        let pages = self.pages.lock().await;
+        CacheStats {
+            entries: pages.pages.len(),
+            capacity: self.config.capacity,
+            dependencies: pages.dependents.len(),
+            ..pages.stats
+        }
+    }
+}
+
+impl Page {
+    /// Whether the request's validators match the page, `If-None-Match` wins
+    /// over `If-Modified-Since` when both are sent.
+    fn not_modified(&self, request: &Request<'_>) -> bool {
+        if let Some(tags) = request.headers().get_one("If-None-Match") {
+            return tags.split(',').map(str::trim).any(|tag| {
+                tag == "*" || tag.trim_start_matches("W/") == &*self.page.etag
+            });
+        }
+        match (request.headers().get_one("If-Modified-Since"), self.page.last_modified) {
+            (Some(since), Some(modified)) => DateTime::parse_from_rfc2822(since)
+                .is_ok_and(|since| modified.timestamp() <= since.timestamp()),
+            _ => false,
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for Page {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = match self.not_modified(request) {
+            true => Response::build().status(Status::NotModified).finalize(),
+            false => RawHtml(self.page.html.clone()).respond_to(request)?,
+        };
+        response.set_header(Header::new("ETag", self.page.etag.to_string()));
+        if let Some(modified) = self.page.last_modified {
+            let modified = modified.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+            response.set_header(Header::new("Last-Modified", modified));
+        }
+        response.set_header(Header::new("Cache-Control", self.cache_control));
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::post::article::Content;
+    use rocket::local::blocking::Client;
+
+    const TTL: Duration = Duration::from_secs(60);
+
+    fn page(html: &str) -> CachedPage {
+        CachedPage::new(html.into(), None)
+    }
+
+    #[tokio::test]
+    async fn drops_pages_of_an_older_theme() {
+        let cache = PageCache::new(&CacheConfig::default());
+        let dependencies = Dependencies::default();
+        cache.insert("index".to_string(), page("old"), &dependencies, 0).await;
+        assert!(cache.get("index", TTL, 0).await.is_some());
+
+        // A request holding the new theme drops the old pages.
+        assert!(cache.get("index", TTL, 1).await.is_none());
+        // One still holding the old theme can neither cache nor read them.
+        cache.insert("index".to_string(), page("old"), &dependencies, 0).await;
+        assert!(cache.get("index", TTL, 0).await.is_none());
+        assert!(cache.get("index", TTL, 1).await.is_none());
+
+        cache.insert("index".to_string(), page("new"), &dependencies, 1).await;
+        assert_eq!(cache.get("index", TTL, 1).await.unwrap().html.as_ref(), "new");
+    }
+
+    #[tokio::test]
+    async fn stores_nothing_without_capacity() {
+        let cache = PageCache::new(&CacheConfig { capacity: 0, ..CacheConfig::default() });
+        cache.insert("index".to_string(), page("index"), &Dependencies::default(), 0).await;
+        assert!(cache.get("index", TTL, 0).await.is_none());
+        assert_eq!(cache.stats().await.capacity, 0);
+    }
+
+    #[tokio::test]
+    async fn starts_the_counters_over_on_clear() {
+        let cache = PageCache::new(&CacheConfig::default());
+        cache.insert("index".to_string(), page("index"), &Dependencies::default(), 0).await;
+        assert!(cache.get("index", TTL, 0).await.is_some());
+        assert!(cache.get("missing", TTL, 0).await.is_none());
+        let stats = cache.stats().await;
+        assert_eq!((stats.entries, stats.hits, stats.misses), (1, 1, 1));
+
+        cache.clear().await;
+        let stats = cache.stats().await;
+        assert_eq!((stats.entries, stats.hits, stats.misses), (0, 0, 0));
+        assert_eq!(stats.dependencies, 0);
+    }
+
+    #[test]
+    fn rejects_unknown_routes() {
+        let mut config = CacheConfig::default();
+        config.routes.insert("blog_index".to_string(), 300);
+        config.cache_control_routes.insert("pages".to_string(), "no-store".to_string());
+        assert!(config.validate().is_ok());
+
+        config.routes.insert("blog-index".to_string(), 300);
+        let error = config.validate().unwrap_err().to_string();
+        assert!(error.starts_with("Unknown cache routes blog-index,"), "{}", error);
+    }
+
+    fn revalidate(page: &CachedPage, headers: &[(&'static str, &str)]) -> bool {
+        let client = Client::debug_with(vec![]).unwrap();
+        let mut request = client.get("/");
+        for (name, value) in headers {
+            request.add_header(Header::new(*name, value.to_string()));
+        }
+        let page = Page { page: page.clone(), cache_control: String::new() };
+        page.not_modified(&request)
+    }
+
+    #[test]
+    fn matches_if_none_match() {
+        let page = page("index");
+        let etag = &page.etag;
+        let matches = |tags: &str| revalidate(&page, &[("If-None-Match", tags)]);
+        assert!(matches(etag));
+        assert!(matches(&format!("W/{}", etag)));
+        assert!(matches("*"));
+        assert!(matches(&format!("\"other\", W/{} ,\"another\"", etag)));
+        assert!(!matches("\"other\", W/\"another\""));
+    }
+
+    #[test]
+    fn prefers_if_none_match_to_if_modified_since() {
+        let modified = DateTime::parse_from_rfc3339("2024-07-01T12:00:00Z").unwrap().to_utc();
+        let page = CachedPage::new("index".into(), Some(modified));
+        let since = modified.to_rfc2822();
+        let earlier = (modified - chrono::Duration::days(1)).to_rfc2822();
+        assert!(revalidate(&page, &[("If-Modified-Since", &since)]));
+        assert!(!revalidate(&page, &[("If-Modified-Since", &since), ("If-None-Match", "\"other\"")]));
+        assert!(!revalidate(&page, &[("If-Modified-Since", &earlier)]));
+        assert!(revalidate(&page, &[("If-Modified-Since", &earlier), ("If-None-Match", &page.etag)]));
+    }
+
+    fn article(section: &str, slug: &str, tags: &[&str]) -> Article {
+        let mut article = Article::new(
+            Ulid::new(),
+            slug.to_string(),
+            section.to_string(),
+            slug.to_string(),
+            "author".to_string(),
+            Utc::now(),
+            Content::Markdown(String::new().into()),
+        );
+        article.tags = tags.iter().map(|t| t.to_string()).collect();
+        article
+    }
+
+    fn articles() -> [Article; 3] {
+        [
+            article("blog", "first", &["Rust"]),
+            article("blog", "second", &["Rust"]),
+            article(PAGES_SECTION, "about", &[]),
+        ]
+    }
+
+    /// The route's own resources, without the menus and the tag cloud of
+    /// [`Dependencies::default`] that every page depends on.
+    fn own() -> Dependencies {
+        Dependencies { resources: HashSet::new(), last_modified: None }
+    }
+
+    /// A cache holding the article pages of `first`, `second` and `about`, the
+    /// second page of the blog listing `second`, the `rust` tag page listing
+    /// `second`, the tag cloud and a page showing only the menus.
+    async fn cache(first: &Article, second: &Article, about: &Article) -> PageCache {
+        let cache = PageCache::new(&CacheConfig::default());
+        let pages = [
+            ("first", own().article(first)),
+            ("second", own().article(second)),
+            ("blog?page=2", own().with(Dependency::Section("blog".to_string())).article(second)),
+            (
+                "tags/rust",
+                own().with(Dependency::Term(Taxonomy::Tags, "rust".to_string())).article(second),
+            ),
+            ("tags", own().with(Dependency::Terms(Taxonomy::Tags))),
+            ("projects", own().with(Dependency::Menus)),
+            ("about", own().article(about)),
+        ];
+        for (id, dependencies) in pages {
+            cache.insert(id.to_string(), page(id), &dependencies, 0).await;
+        }
+        cache
+    }
+
+    async fn purge(cache: &PageCache, previous: &Article, current: &Article) -> Vec<&'static str> {
+        cache.invalidate(&Dependencies::changed(Some(previous), Some(current))).await;
+        let mut left = Vec::new();
+        for id in ["first", "second", "blog?page=2", "tags/rust", "tags", "projects", "about"] {
+            if cache.get(id, TTL, 0).await.is_some() {
+                left.push(id);
+            }
+        }
+        left
+    }
+
+    #[tokio::test]
+    async fn purges_only_the_pages_of_an_article_edited_in_place() {
+        let [first, second, about] = articles();
+        let cache = cache(&first, &second, &about).await;
+        let mut edited = first.clone();
+        edited.title = "First".to_string();
+        let left = purge(&cache, &first, &edited).await;
+        assert_eq!(left, ["second", "blog?page=2", "tags/rust", "tags", "projects", "about"]);
+    }
+
+    #[tokio::test]
+    async fn purges_the_term_listings_of_a_retagged_article() {
+        let [first, second, about] = articles();
+        let cache = cache(&first, &second, &about).await;
+        let mut retagged = first.clone();
+        retagged.tags = vec!["Go".to_string()];
+        let left = purge(&cache, &first, &retagged).await;
+        assert_eq!(left, ["second", "blog?page=2", "projects", "about"]);
+    }
+
+    #[tokio::test]
+    async fn purges_the_section_and_term_listings_of_a_drafted_article() {
+        let [first, second, about] = articles();
+        let cache = cache(&first, &second, &about).await;
+        let mut drafted = first.clone();
+        drafted.draft = true;
+        let left = purge(&cache, &first, &drafted).await;
+        assert_eq!(left, ["second", "projects", "about"]);
+    }
+
+    #[tokio::test]
+    async fn purges_the_menus_with_a_page_article() {
+        let [first, second, about] = articles();
+        let cache = cache(&first, &second, &about).await;
+        let mut edited = about.clone();
+        edited.title = "About".to_string();
+        let left = purge(&cache, &about, &edited).await;
+        assert_eq!(left, ["first", "second", "blog?page=2", "tags/rust", "tags"]);
+    }
+}
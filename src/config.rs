@@ -2,12 +2,37 @@ use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+use crate::cache::CacheConfig;
+use crate::page::PageDef;
+use crate::settings::SiteSettings;
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Config {
     pub admin_port: Arc<u16>,
     pub theme: Arc<str>,
     pub theme_dir: Arc<Path>,
     pub db_dir: Arc<Path>,
+    pub articles_dir: Arc<Path>,
+    pub blog_page_size: usize,
+    pub feed_items: usize,
+    pub feed_full_content: bool,
+    pub admin_username: Arc<str>,
+    pub admin_email: Arc<str>,
+    pub admin_password: Option<Arc<str>>,
+    pub session_hours: i64,
+    pub google_client_id: Option<Arc<str>>,
+    pub google_client_secret: Option<Arc<str>>,
+    pub google_issuer: Arc<str>,
+    pub google_redirect_url: Arc<str>,
+    /// Initial site settings, see [`SiteSettings`].
+    pub site: SiteSettings,
+    /// Pages of the catch-all route, see [`PageDef`].
+    pub pages: Vec<PageDef>,
+    /// Reload changed templates and articles and refresh open pages, see
+    /// [`crate::reload`].
+    pub hot_reload: bool,
+    /// Size and TTLs of the rendered page cache.
+    pub cache: CacheConfig,
 }
 
 impl Default for Config {
@@ -27,12 +52,29 @@ impl Default for Config {
 
         let theme_dir: Arc<Path> = home_dir.join("my_web/theme").into();
         let db_dir: Arc<Path> = home_dir.join("my_web/db").into();
+        let articles_dir: Arc<Path> = home_dir.join("my_web/articles").into();
         if cfg!(debug_assertions) {
             return Config {
                 admin_port: 8001.into(),
                 theme: "default".into(),
                 theme_dir: Path::new("theme").into(),
                 db_dir: Path::new("db").into(),
+                articles_dir: Path::new("articles").into(),
+                blog_page_size: 10,
+                feed_items: 20,
+                feed_full_content: true,
+                admin_username: "admin".into(),
+                admin_email: "admin@localhost".into(),
+                admin_password: None,
+                session_hours: 24 * 7,
+                google_client_id: None,
+                google_client_secret: None,
+                google_issuer: "https://accounts.google.com".into(),
+                google_redirect_url: "http://localhost:8001/api/oauth/google/callback".into(),
+                site: SiteSettings::default(),
+                pages: Vec::new(),
+                hot_reload: false,
+                cache: CacheConfig::default(),
             };
         }
         Config {
@@ -40,6 +82,22 @@ impl Default for Config {
             theme: "default".into(),
             theme_dir,
             db_dir,
+            articles_dir,
+            blog_page_size: 10,
+            feed_items: 20,
+            feed_full_content: true,
+            admin_username: "admin".into(),
+            admin_email: "admin@localhost".into(),
+            admin_password: None,
+            session_hours: 24 * 7,
+            google_client_id: None,
+            google_client_secret: None,
+            google_issuer: "https://accounts.google.com".into(),
+            google_redirect_url: "http://localhost:8001/api/oauth/google/callback".into(),
+            site: SiteSettings::default(),
+            pages: Vec::new(),
+            hot_reload: false,
+            cache: CacheConfig::default(),
         }
     }
 }
@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tokio::fs::{self, File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use crate::Error;
+use super::{Data, Store};
+
+/// Compaction kicks in once the log holds this many entries per live record.
+const COMPACT_RATIO: usize = 2;
+/// Small stores are never compacted below this many log entries.
+const COMPACT_MIN: usize = 64;
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum Entry<K, V> {
+    Insert { key: K, value: V },
+    Delete { key: K },
+}
+
+struct Log {
+    path: PathBuf,
+    file: File,
+    entries: usize,
+}
+
+/// A [`Data`] store persisted to an append-only JSON lines log under `db_dir`.
+///
+/// Every write is appended to `<name>.log` before it is applied in memory, and the
+/// log is rewritten with only the live records once it grows past
+/// [`COMPACT_RATIO`] times the number of records.
+pub struct FileData<K, V>
+where K: Eq + Hash + Clone, V: Clone
+{
+    data: Data<K, V>,
+    log: Arc<Mutex<Log>>,
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> Clone for FileData<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            data: self.data.clone(),
+            log: self.log.clone(),
+        }
+    }
+}
+
+impl<K, V> FileData<K, V>
+where
+    K: Eq + Hash + Clone + Send + Sync + Serialize + DeserializeOwned + 'static,
+    V: Clone + Send + Sync + Serialize + DeserializeOwned + 'static,
+{
+    pub async fn open(db_dir: &Path, name: &str) -> Result<Self, Error> {
+        fs::create_dir_all(db_dir).await?;
+        let path = db_dir.join(name).with_extension("log");
+        let mut data = HashMap::new();
+        let mut entries = 0;
+        if let Ok(content) = fs::read_to_string(&path).await {
+            let lines: Vec<&str> = content.split_inclusive('\n').collect();
+            let last = lines.iter().rposition(|l| !l.trim().is_empty());
+            // Bytes of the log up to the end of the last whole entry.
+            let mut valid = 0;
+            for (i, line) in lines.iter().enumerate() {
+                if !line.trim().is_empty() {
+                    match serde_json::from_str::<Entry<K, V>>(line) {
+                        Ok(Entry::Insert { key, value }) => {
+                            data.insert(key, value);
+                        }
+                        Ok(Entry::Delete { key }) => {
+                            data.remove(&key);
+                        }
+                        // A torn final line means we crashed mid-write, the entry was never acknowledged.
+                        Err(_) if Some(i) == last => break,
+                        Err(e) => return Err(format!("Corrupted store {}: {}", path.display(), e).into()),
+                    }
+                    entries += 1;
+                }
+                valid += line.len();
+            }
+            // Drop a torn tail and end the last entry with a newline, or the
+            // next append would be joined to it.
+            if valid < content.len() || (!content.is_empty() && !content.ends_with('\n')) {
+                let mut repaired = content[..valid].to_string();
+                if !repaired.is_empty() && !repaired.ends_with('\n') {
+                    repaired.push('\n');
+                }
+                replace(&path, &repaired).await?;
+            }
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path).await?;
+        let store = Self {
+            data: Data::from(false, data),
+            log: Arc::new(Mutex::new(Log { path, file, entries })),
+        };
+        store.compact().await?;
+        Ok(store)
+    }
+
+    /// Appends `entry` to the log and applies it in memory while holding the log lock,
+    /// so the on-disk order always matches the in-memory state.
+    async fn write(&self, entry: Entry<K, V>) -> Result<(), Error> {
+        let mut line = serde_json::to_string(&entry)?;
+        line.push('\n');
+        {
+            let mut log = self.log.lock().await;
+            log.file.write_all(line.as_bytes()).await?;
+            log.file.sync_data().await?;
+            log.entries += 1;
+            match entry {
+                Entry::Insert { key, value } => self.data.insert(key, value).await?,
+                Entry::Delete { key } => self.data.delete(&key).await?,
+            }
+        }
+        self.compact().await
+    }
+
+    /// Rewrites the log with one insert per live record if it grew too large.
+    pub async fn compact(&self) -> Result<(), Error> {
+        let mut log = self.log.lock().await;
+        let len = self.data.len().await?;
+        if log.entries <= COMPACT_MIN.max(len * COMPACT_RATIO) {
+            return Ok(());
+        }
+        let mut content = String::new();
+        for key in self.data.keys().await? {
+            if let Some(value) = self.data.get(&key).await? {
+                content.push_str(&serde_json::to_string(&Entry::Insert { key, value })?);
+                content.push('\n');
+            }
+        }
+        replace(&log.path, &content).await?;
+        log.file = OpenOptions::new().append(true).open(&log.path).await?;
+        log.entries = len;
+        Ok(())
+    }
+}
+
+/// Writes `content` to `path` through a synced temporary file, so a crash
+/// leaves either the old or the new file.
+async fn replace(path: &Path, content: &str) -> Result<(), Error> {
+    let tmp = path.with_extension("log.tmp");
+    let mut file = File::create(&tmp).await?;
+    file.write_all(content.as_bytes()).await?;
+    file.sync_all().await?;
+    fs::rename(&tmp, path).await?;
+    Ok(())
+}
+
+#[async_trait]
+impl<K, V> Store<K, V> for FileData<K, V>
+where
+    K: Eq + Hash + Clone + Send + Sync + Serialize + DeserializeOwned + 'static,
+    V: Clone + Send + Sync + Serialize + DeserializeOwned + 'static,
+{
+    async fn get(&self, k: &K) -> Result<Option<V>, Error> {
+        self.data.get(k).await
+    }
+
+    async fn insert(&self, k: K, v: V) -> Result<(), Error> {
+        self.write(Entry::Insert { key: k, value: v }).await
+    }
+
+    async fn delete(&self, k: &K) -> Result<(), Error> {
+        if self.data.get(k).await?.is_none() {
+            return Ok(());
+        }
+        self.write(Entry::Delete { key: k.clone() }).await
+    }
+
+    async fn len(&self) -> Result<usize, Error> {
+        self.data.len().await
+    }
+
+    async fn keys(&self) -> Result<Vec<K>, Error> {
+        self.data.keys().await
+    }
+
+    async fn values(&self) -> Result<Vec<V>, Error> {
+        self.data.values().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ulid::Ulid;
+
+    fn db_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("myweb-db-{}", Ulid::new()))
+    }
+
+    async fn open(dir: &Path) -> FileData<String, u32> {
+        FileData::open(dir, "test").await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn replays_inserts_and_deletes() {
+        let dir = db_dir();
+        let store = open(&dir).await;
+        store.insert("a".to_string(), 1).await.unwrap();
+        store.insert("b".to_string(), 2).await.unwrap();
+        store.insert("a".to_string(), 3).await.unwrap();
+        store.delete(&"b".to_string()).await.unwrap();
+
+        let store = open(&dir).await;
+        assert_eq!(store.get(&"a".to_string()).await.unwrap(), Some(3));
+        assert_eq!(store.get(&"b".to_string()).await.unwrap(), None);
+        assert_eq!(store.len().await.unwrap(), 1);
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn recovers_from_a_torn_tail() {
+        let dir = db_dir();
+        let store = open(&dir).await;
+        store.insert("a".to_string(), 1).await.unwrap();
+        let path = dir.join("test.log");
+        let mut log = OpenOptions::new().append(true).open(&path).await.unwrap();
+        log.write_all(br#"{"op":"insert","key":"b","va"#).await.unwrap();
+
+        let store = open(&dir).await;
+        assert_eq!(store.get(&"b".to_string()).await.unwrap(), None);
+        store.insert("c".to_string(), 3).await.unwrap();
+
+        let store = open(&dir).await;
+        assert_eq!(store.get(&"a".to_string()).await.unwrap(), Some(1));
+        assert_eq!(store.get(&"c".to_string()).await.unwrap(), Some(3));
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn ends_an_unterminated_entry() {
+        let dir = db_dir();
+        fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("test.log");
+        fs::write(&path, r#"{"op":"insert","key":"a","value":1}"#).await.unwrap();
+
+        let store = open(&dir).await;
+        store.insert("b".to_string(), 2).await.unwrap();
+
+        let store = open(&dir).await;
+        assert_eq!(store.get(&"a".to_string()).await.unwrap(), Some(1));
+        assert_eq!(store.get(&"b".to_string()).await.unwrap(), Some(2));
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn fails_on_a_corrupted_entry() {
+        let dir = db_dir();
+        fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("test.log");
+        let log = "{\"op\":\"insert\",\"key\":\"a\",\"va\n{\"op\":\"insert\",\"key\":\"b\",\"value\":2}\n";
+        fs::write(&path, log).await.unwrap();
+
+        assert!(FileData::<String, u32>::open(&dir, "test").await.is_err());
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn compacts_to_the_live_records() {
+        let dir = db_dir();
+        let store = open(&dir).await;
+        for i in 0..=COMPACT_MIN as u32 {
+            store.insert("a".to_string(), i).await.unwrap();
+        }
+        let path = dir.join("test.log");
+        let log = fs::read_to_string(&path).await.unwrap();
+        assert_eq!(log.lines().count(), 1);
+        assert!(!dir.join("test.log.tmp").exists());
+
+        store.insert("b".to_string(), 1).await.unwrap();
+        let store = open(&dir).await;
+        assert_eq!(store.get(&"a".to_string()).await.unwrap(), Some(COMPACT_MIN as u32));
+        assert_eq!(store.get(&"b".to_string()).await.unwrap(), Some(1));
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
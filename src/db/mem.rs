@@ -1,8 +1,10 @@
 use std::collections::HashMap;
 use std::hash::Hash;
 use std::sync::Arc;
+use async_trait::async_trait;
 use tokio::sync::RwLock;
 use crate::Error;
+use super::Store;
 
 pub struct Data<K, V>
 where K: Eq + Hash + Clone, V: Clone
@@ -11,6 +13,15 @@ where K: Eq + Hash + Clone, V: Clone
     key: Option<Arc<RwLock<Vec<K>>>>
 }
 
+impl<K: Eq + Hash + Clone, V: Clone> Clone for Data<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            data: self.data.clone(),
+            key: self.key.clone(),
+        }
+    }
+}
+
 impl <K: Eq + Hash + Clone, V: Clone> Data<K, V> {
     pub fn new(ordered: bool) -> Self {
         let key = match ordered {
@@ -25,7 +36,7 @@ impl <K: Eq + Hash + Clone, V: Clone> Data<K, V> {
 
     pub fn from(ordered: bool, data: HashMap<K, V>) -> Self {
         let key = match ordered {
-            true => Some(Arc::new(RwLock::new(Vec::new()))),
+            true => Some(Arc::new(RwLock::new(data.keys().cloned().collect()))),
             false => None
         };
         Self {
@@ -33,42 +44,57 @@ impl <K: Eq + Hash + Clone, V: Clone> Data<K, V> {
             key,
         }
     }
+}
 
-    pub async fn get(&self, k: &K) -> Result<Option<V>, Error> {
-        let read = self.data.try_read().map_err(|e| e.to_string())?;
-        Ok(read.get(k).cloned())
+#[async_trait]
+impl<K, V> Store<K, V> for Data<K, V>
+where
+    K: Eq + Hash + Clone + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+{
+    async fn get(&self, k: &K) -> Result<Option<V>, Error> {
+        Ok(self.data.read().await.get(k).cloned())
     }
 
-    pub async fn index_get(&self, index: usize) -> Result<Option<V>, Error> {
-        let key = self.key.as_ref().ok_or("Not an ordered data".to_string())?.try_read().map_err(|e| e.to_string())?;
-        if index < key.len() {
-            Ok(self.data.try_read().map_err(|e| e.to_string())?.get(&key[index]).cloned())
-        } else {
-            Ok(None)
+    async fn insert(&self, k: K, v: V) -> Result<(), Error> {
+        let mut data = self.data.write().await;
+        if let Some(key) = &self.key {
+            if !data.contains_key(&k) {
+                key.write().await.push(k.clone());
+            }
         }
+        data.insert(k, v);
+        Ok(())
     }
 
-    pub async fn insert(&self, k: K, v: V) -> Result<(), Error> {
+    async fn delete(&self, k: &K) -> Result<(), Error> {
+        let mut data = self.data.write().await;
         if let Some(key) = &self.key {
-            key.try_write().map_err(|e| e.to_string())?.push(k.clone());
+            key.write().await.retain(|x| x != k);
         }
-        self.data.try_write().map_err(|e| e.to_string())?.insert(k, v);
+        data.remove(k);
         Ok(())
     }
 
-    pub async fn delete(&self, k: &K) -> Result<(), Error> {
+    async fn len(&self) -> Result<usize, Error> {
         if let Some(key) = &self.key {
-            key.try_write().map_err(|e| e.to_string())?.retain(|x| x != k);
+            return Ok(key.read().await.len())
         }
-        self.data.try_write().map_err(|e| e.to_string())?.remove(k);
-        Ok(())
+        Ok(self.data.read().await.len())
     }
 
-    pub async fn len(&self) -> Result<usize, Error> {
+    async fn keys(&self) -> Result<Vec<K>, Error> {
         if let Some(key) = &self.key {
-            return Ok(key.try_read().map_err(|e| e.to_string())?.len())
+            return Ok(key.read().await.clone())
         }
-        Ok(self.data.try_read().map_err(|e| e.to_string())?.len())
+        Ok(self.data.read().await.keys().cloned().collect())
     }
 
-}
\ No newline at end of file
+    async fn values(&self) -> Result<Vec<V>, Error> {
+        let data = self.data.read().await;
+        if let Some(key) = &self.key {
+            return Ok(key.read().await.iter().filter_map(|k| data.get(k).cloned()).collect())
+        }
+        Ok(data.values().cloned().collect())
+    }
+}
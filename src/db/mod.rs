@@ -1 +1,64 @@
+pub mod file;
 pub mod mem;
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::path::Path;
+use std::sync::Arc;
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::sync::Mutex;
+use crate::Error;
+
+pub use file::FileData;
+pub use mem::Data;
+
+#[async_trait]
+pub trait Store<K, V>: Send + Sync {
+    async fn get(&self, k: &K) -> Result<Option<V>, Error>;
+    async fn insert(&self, k: K, v: V) -> Result<(), Error>;
+    async fn delete(&self, k: &K) -> Result<(), Error>;
+    async fn len(&self) -> Result<usize, Error>;
+    async fn is_empty(&self) -> Result<bool, Error> {
+        Ok(self.len().await? == 0)
+    }
+    async fn keys(&self) -> Result<Vec<K>, Error>;
+    async fn values(&self) -> Result<Vec<V>, Error>;
+}
+
+/// Registry of the persistent stores under `db_dir`.
+///
+/// Opening the same name twice returns the same [`FileData`], so the public and
+/// admin servers share one in-memory view and one log writer per store.
+#[derive(Clone)]
+pub struct Db {
+    dir: Arc<Path>,
+    stores: Arc<Mutex<HashMap<String, Arc<dyn Any + Send + Sync>>>>,
+}
+
+impl Db {
+    pub fn new(dir: Arc<Path>) -> Self {
+        Self {
+            dir,
+            stores: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub async fn open<K, V>(&self, name: &str) -> Result<FileData<K, V>, Error>
+    where
+        K: Eq + Hash + Clone + Send + Sync + Serialize + DeserializeOwned + 'static,
+        V: Clone + Send + Sync + Serialize + DeserializeOwned + 'static,
+    {
+        let mut stores = self.stores.lock().await;
+        if let Some(store) = stores.get(name) {
+            return store
+                .downcast_ref::<FileData<K, V>>()
+                .cloned()
+                .ok_or_else(|| format!("Store {} already opened with different types", name).into());
+        }
+        let store = FileData::<K, V>::open(&self.dir, name).await?;
+        stores.insert(name.to_string(), Arc::new(store.clone()));
+        Ok(store)
+    }
+}
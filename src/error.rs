@@ -7,10 +7,21 @@ pub enum Error {
     Fmt(std::fmt::Error),
     #[from]
     Io(std::io::Error),
-    #[from]
-    Rocket(rocket::Error),
+    Rocket(Box<rocket::Error>),
     #[from]
     Json(serde_json::Error),
     #[from]
+    Yaml(serde_yaml::Error),
+    #[from]
+    Toml(toml::de::Error),
+    #[from]
+    Http(reqwest::Error),
+    #[from]
     String(String),
-}
\ No newline at end of file
+}
+
+impl From<rocket::Error> for Error {
+    fn from(e: rocket::Error) -> Self {
+        Error::Rocket(Box::new(e))
+    }
+}
@@ -1,8 +1,10 @@
 use std::fmt::Display;
 
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-#[derive(Default, Clone, Debug)]
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct Json(Value);
 impl Json {
     pub fn new(content: Value) -> Json {
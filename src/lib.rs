@@ -0,0 +1,18 @@
+pub mod account;
+pub mod admin;
+pub mod cache;
+pub mod config;
+pub mod db;
+pub mod json;
+pub mod page;
+pub mod post;
+pub mod public;
+pub mod reload;
+pub mod render;
+pub mod settings;
+pub mod string;
+pub mod template;
+pub mod theme;
+pub mod error;
+pub use error::{Result, Error};
+use string::*;
@@ -1,33 +1,49 @@
-mod account;
-mod admin;
-mod config;
-mod db;
-mod json;
-mod post;
-mod public;
-mod render;
-mod string;
-mod template;
-mod theme;
-mod error;
-pub use error::{Result, Error};
-use config::Config;
+use myweb::{admin, public, reload, theme, Result};
+use myweb::config::Config;
+use myweb::db::Db;
+use myweb::post::ArticleRepository;
+use myweb::render::PageCache;
 use figment::{
     providers::{Env, Format, Serialized, Toml},
     Figment, Profile,
 };
-use string::*;
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    if std::env::args().any(|a| a == "--theme-schema") {
+        println!("{}", theme::Theme::schema()?);
+        return Ok(());
+    }
     let figment = Figment::from(rocket::Config::default())
         .merge(Serialized::defaults(Config::default()))
         .merge(Toml::file("config/server.toml").nested())
         .merge(Env::prefixed("MY_WEB_").global())
-        .select(Profile::from_env_or("MY_WEB_PROFILE", "default"));
+        // Rocket's default profile, `debug` or `release` by build. Rocket only
+        // generates the key of the private session cookies in `debug` and
+        // refuses to start any other profile without a `secret_key`.
+        .select(Profile::from_env_or(
+            "MY_WEB_PROFILE",
+            rocket::Config::DEFAULT_PROFILE,
+        ));
 
-    let public = public::launch(&figment).await?;
-    let admin = admin::launch(&figment).await?;
+    let config = figment
+        .extract::<Config>()
+        .map_err(|e| format!("Failed to extract config: {}", e))?;
+    config.cache.validate()?;
+    let db = Db::new(config.db_dir.clone());
+    let articles = ArticleRepository::index(config.articles_dir.clone()).await?;
+
+    let page_cache = PageCache::new(&config.cache);
+    let themes = theme::ThemeRegistry::open(&config, &db).await?;
+
+    let live_reload = match config.hot_reload {
+        true => Some(reload::watch(&config, &themes, &articles, &page_cache)?),
+        false => None,
+    };
+
+    let public =
+        public::launch(&figment, &db, &articles, &page_cache, &themes, live_reload.as_ref()).await?;
+    let admin = admin::launch(&figment, &db, &articles, &page_cache, &themes).await?;
 
     let public_task = tokio::task::spawn(async move {
         public
@@ -0,0 +1,326 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+use crate::db::{FileData, Store};
+use crate::post::article::Article;
+use crate::post::ArticleRepository;
+use crate::public::Menu;
+use crate::string::{Slugify, TitleCase};
+use crate::Error;
+
+pub type PageStore = FileData<String, PageDef>;
+
+/// Section of the articles served as standalone pages at `/<slug>`.
+pub const PAGES_SECTION: &str = "pages";
+
+/// A page served by the public catch-all route.
+///
+/// Pages come from the admin API, the articles of [`PAGES_SECTION`] and the
+/// `[[default.pages]]` tables of the config, in that order of precedence.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PageDef {
+    /// Path below the site root without the leading slash, e.g. `about`.
+    pub slug: String,
+    /// Defaults to the title cased last segment of the slug.
+    #[serde(default)]
+    pub title: String,
+    /// Page template of the theme.
+    #[serde(default = "default_template")]
+    pub template: String,
+    /// Component of each partial, on top of the defaults of the source.
+    #[serde(default)]
+    pub components: BTreeMap<String, String>,
+    #[serde(default)]
+    pub layout_min: bool,
+    /// Seconds a rendered copy is served from the page cache.
+    #[serde(default)]
+    pub cache_ttl: Option<u64>,
+    pub source: PageSource,
+    /// Extra template variables.
+    #[serde(default)]
+    pub data: Map<String, Value>,
+}
+
+/// Where the content of a [`PageDef`] comes from.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PageSource {
+    /// Inline HTML handed to the template as `article`.
+    Html { content: String },
+    /// Inline Markdown, rendered as `article`.
+    Markdown { content: String },
+    /// A published article, rendered as `article`.
+    Article { section: String, slug: String },
+    /// Paginated previews of the published articles of a section.
+    Articles { section: String },
+}
+
+fn default_template() -> String {
+    "default".to_string()
+}
+
+/// Page settings in the front matter of a [`PAGES_SECTION`] article.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct PageFrontMatter {
+    template: Option<String>,
+    components: BTreeMap<String, String>,
+    layout_min: bool,
+    cache_ttl: Option<u64>,
+    menu: Option<PageMenu>,
+}
+
+/// `menu: true` or `menu: { name: ..., weight: ... }` adds the page to the
+/// site's menus.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PageMenu {
+    Show(bool),
+    Entry {
+        name: Option<String>,
+        #[serde(default)]
+        weight: i32,
+    },
+}
+
+impl PageFrontMatter {
+    fn of(article: &Article) -> Result<PageFrontMatter, Error> {
+        serde_json::from_value(Value::Object(article.extra.clone())).map_err(|e| {
+            format!("Invalid page front matter in {}: {}", article.url(), e).into()
+        })
+    }
+}
+
+/// Menu entries of the published pages that ask for one.
+pub async fn page_menus(articles: &ArticleRepository) -> Result<Vec<Menu>, Error> {
+    let mut menus = Vec::new();
+    for article in articles.published(PAGES_SECTION).await? {
+        let (name, weight) = match PageFrontMatter::of(&article)?.menu {
+            Some(PageMenu::Show(true)) => (None, 0),
+            Some(PageMenu::Entry { name, weight }) => (name, weight),
+            Some(PageMenu::Show(false)) | None => continue,
+        };
+        let mut menu = Menu::new(name.as_deref().unwrap_or(&article.title), &format!("/{}", article.slug));
+        menu.weight = weight;
+        menus.push(menu);
+    }
+    Ok(menus)
+}
+
+impl PageDef {
+    /// Partial to component pairs for [`crate::theme::Theme::resolve`].
+    pub fn overrides(&self) -> Vec<(&str, &str)> {
+        let article = match self.source {
+            PageSource::Articles { .. } => "article_preview",
+            _ => "blog",
+        };
+        let mut overrides: Vec<(&str, &str)> = self
+            .components
+            .iter()
+            .map(|(p, c)| (p.as_str(), c.as_str()))
+            .collect();
+        for default in [("parent", "layout"), ("article", article)] {
+            if !self.components.contains_key(default.0) {
+                overrides.push(default);
+            }
+        }
+        overrides
+    }
+
+    /// The page of a [`PAGES_SECTION`] article, configured by its front matter.
+    pub fn from_article(article: &Article) -> Result<PageDef, Error> {
+        let front_matter = PageFrontMatter::of(article)?;
+        Ok(PageDef {
+            slug: article.slug.clone(),
+            title: article.title.clone(),
+            template: front_matter.template.unwrap_or_else(default_template),
+            components: front_matter.components,
+            layout_min: front_matter.layout_min,
+            cache_ttl: front_matter.cache_ttl,
+            source: PageSource::Article {
+                section: article.section.clone(),
+                slug: article.slug.clone(),
+            },
+            data: Map::new(),
+        })
+    }
+
+    pub fn title(&self) -> String {
+        match self.title.trim().is_empty() {
+            true => self
+                .slug
+                .rsplit('/')
+                .next()
+                .unwrap_or_default()
+                .replace(['-', '_'], " ")
+                .title_case(),
+            false => self.title.clone(),
+        }
+    }
+
+    /// Slug segments must be slugs, `docs/intro` is fine.
+    pub fn has_valid_slug(&self) -> bool {
+        self.slug.split('/').all(|s| s.is_slug())
+    }
+}
+
+#[async_trait]
+pub trait FindPage {
+    /// The stored page of `slug`, the page article or the configured page.
+    async fn page(
+        &self,
+        slug: &str,
+        articles: &ArticleRepository,
+        configured: &[PageDef],
+    ) -> Result<Option<PageDef>, Error>;
+    /// Every page by the same precedence, ordered by slug.
+    async fn pages(
+        &self,
+        articles: &ArticleRepository,
+        configured: &[PageDef],
+    ) -> Result<Vec<PageDef>, Error>;
+}
+
+#[async_trait]
+impl FindPage for PageStore {
+    async fn page(
+        &self,
+        slug: &str,
+        articles: &ArticleRepository,
+        configured: &[PageDef],
+    ) -> Result<Option<PageDef>, Error> {
+        if let Some(page) = self.get(&slug.to_string()).await? {
+            return Ok(Some(page));
+        }
+        if let Some(article) = articles.by_slug(PAGES_SECTION, slug).await? {
+            if !article.draft {
+                return PageDef::from_article(&article).map(Some);
+            }
+        }
+        Ok(configured.iter().find(|p| p.slug == slug).cloned())
+    }
+
+    async fn pages(
+        &self,
+        articles: &ArticleRepository,
+        configured: &[PageDef],
+    ) -> Result<Vec<PageDef>, Error> {
+        let mut pages: BTreeMap<String, PageDef> = configured
+            .iter()
+            .map(|p| (p.slug.clone(), p.clone()))
+            .collect();
+        for article in articles.published(PAGES_SECTION).await? {
+            pages.insert(article.slug.clone(), PageDef::from_article(&article)?);
+        }
+        for page in self.values().await? {
+            pages.insert(page.slug.clone(), page);
+        }
+        Ok(pages.into_values().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+    use tokio::fs::{create_dir_all, remove_dir_all, write};
+    use ulid::Ulid;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("myweb-{}-{}", name, Ulid::new()))
+    }
+
+    async fn articles(files: &[(&str, &str)]) -> ArticleRepository {
+        let dir = temp_dir("pages");
+        for (path, source) in files {
+            let path = dir.join(PAGES_SECTION).join(path);
+            create_dir_all(path.parent().unwrap()).await.unwrap();
+            write(path, source).await.unwrap();
+        }
+        let dir: Arc<Path> = dir.into();
+        ArticleRepository::index(dir).await.unwrap()
+    }
+
+    fn page(slug: &str, title: &str) -> PageDef {
+        PageDef {
+            slug: slug.to_string(),
+            title: title.to_string(),
+            template: default_template(),
+            components: BTreeMap::new(),
+            layout_min: false,
+            cache_ttl: None,
+            source: PageSource::Html { content: String::new() },
+            data: Map::new(),
+        }
+    }
+
+    async fn title(store: &PageStore, slug: &str, articles: &ArticleRepository, configured: &[PageDef]) -> Option<String> {
+        store.page(slug, articles, configured).await.unwrap().map(|p| p.title)
+    }
+
+    #[tokio::test]
+    async fn prefers_stored_pages_then_articles_then_the_config() {
+        let db_dir = temp_dir("db");
+        let store = PageStore::open(&db_dir, "pages").await.unwrap();
+        let articles = articles(&[
+            ("about.md", "# About article\n\nBody"),
+            ("contact.md", "# Contact article\n\nBody"),
+            ("draft.md", "---\ndraft: true\n---\n# Draft article\n\nBody"),
+        ])
+        .await;
+        let configured = [
+            page("about", "About config"),
+            page("contact", "Contact config"),
+            page("draft", "Draft config"),
+            page("uses", "Uses config"),
+        ];
+        store.insert("about".to_string(), page("about", "About store")).await.unwrap();
+
+        assert_eq!(title(&store, "about", &articles, &configured).await.unwrap(), "About store");
+        assert_eq!(title(&store, "contact", &articles, &configured).await.unwrap(), "Contact article");
+        // Drafts are not pages yet.
+        assert_eq!(title(&store, "draft", &articles, &configured).await.unwrap(), "Draft config");
+        assert_eq!(title(&store, "uses", &articles, &configured).await.unwrap(), "Uses config");
+        assert!(title(&store, "missing", &articles, &configured).await.is_none());
+
+        let pages = store.pages(&articles, &configured).await.unwrap();
+        let titles: Vec<&str> = pages.iter().map(|p| p.title.as_str()).collect();
+        assert_eq!(titles, ["About store", "Contact article", "Draft config", "Uses config"]);
+        remove_dir_all(&db_dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn configures_article_pages_by_their_front_matter() {
+        let articles = articles(&[
+            (
+                "about.md",
+                "---\ntemplate: wide\nlayout_min: true\ncache_ttl: 60\nmenu:\n  name: Me\n  weight: 2\n---\n# About\n\nBody",
+            ),
+            ("uses.md", "---\nmenu: true\n---\n# Uses\n\nBody"),
+            ("hidden.md", "# Hidden\n\nBody"),
+        ])
+        .await;
+        let about = articles.by_slug(PAGES_SECTION, "about").await.unwrap().unwrap();
+        let page = PageDef::from_article(&about).unwrap();
+        assert_eq!(page.template, "wide");
+        assert!(page.layout_min);
+        assert_eq!(page.cache_ttl, Some(60));
+        assert!(matches!(page.source, PageSource::Article { ref slug, .. } if slug == "about"));
+
+        let mut menus = page_menus(&articles).await.unwrap();
+        menus.sort_by(|a, b| a.url.cmp(&b.url));
+        let menus: Vec<(&str, &str, i32)> = menus.iter().map(|m| (m.name.as_str(), m.url.as_str(), m.weight)).collect();
+        assert_eq!(menus, [("Me", "/about", 2), ("Uses", "/uses", 0)]);
+    }
+
+    #[test]
+    fn titles_untitled_pages_by_their_slug() {
+        assert_eq!(page("docs/getting-started", "").title(), "Getting Started");
+        assert_eq!(page("docs/intro", "Welcome").title(), "Welcome");
+        assert!(page("docs/intro", "").has_valid_slug());
+        assert!(!page("docs/../intro", "").has_valid_slug());
+    }
+}
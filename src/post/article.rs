@@ -1,18 +1,34 @@
-use super::{Html, Markdown};
+use super::{ArticlePrev, Html, Markdown, PreviewArticle};
+use crate::{Error, StringCutter};
+use async_trait::async_trait;
 use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use std::fmt::Display;
 use ulid::Ulid;
 
-#[derive(Default, Clone, Debug)]
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct Article {
     id: Ulid,
+    pub slug: String,
+    pub section: String,
     pub title: String,
     pub author: String,
     pub timestamp: DateTime<Utc>,
     pub content: Content,
+    pub summary: Option<String>,
+    pub tags: Vec<String>,
+    pub categories: Vec<String>,
+    pub draft: bool,
+    /// Account that created the article through the admin API.
+    #[serde(default)]
+    pub owner: Option<Ulid>,
+    #[serde(default)]
+    pub extra: Map<String, Value>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Content {
     Markdown(Markdown),
     Html(Html),
@@ -26,6 +42,8 @@ impl Display for Article {
 impl Article {
     pub fn new(
         id: Ulid,
+        slug: String,
+        section: String,
         title: String,
         author: String,
         timestamp: DateTime<Utc>,
@@ -33,16 +51,28 @@ impl Article {
     ) -> Article {
         Article {
             id,
+            slug,
+            section,
             title,
             author,
             timestamp,
             content,
+            summary: None,
+            tags: Vec::new(),
+            categories: Vec::new(),
+            draft: false,
+            owner: None,
+            extra: Map::new(),
         }
     }
 
     pub fn id(&self) -> Ulid {
         self.id
     }
+
+    pub fn url(&self) -> String {
+        format!("/{}/{}", self.section, self.slug)
+    }
 }
 
 impl Default for Content {
@@ -50,3 +80,22 @@ impl Default for Content {
         Content::Html(Html::from(String::new()))
     }
 }
+
+#[async_trait]
+impl PreviewArticle for Article {
+    /// Uses the front matter summary when present, otherwise the first paragraph.
+    async fn preview(&self) -> Result<ArticlePrev, Error> {
+        let body = match (&self.summary, &self.content) {
+            (Some(summary), _) => summary.clone(),
+            (None, Content::Markdown(markdown)) => markdown.preview().await.map(|p| p.body).unwrap_or_default(),
+            (None, Content::Html(html)) => html.text().cut_to_length(200),
+        };
+        Ok(ArticlePrev {
+            title: self.title.clone(),
+            body,
+            url: self.url(),
+            author: self.author.clone(),
+            date: self.timestamp.format("%B %-d, %Y").to_string(),
+        })
+    }
+}
@@ -0,0 +1,230 @@
+use super::article::Article;
+use crate::Error;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+use ulid::Ulid;
+
+/// Metadata block at the top of a Markdown article.
+///
+/// `---` delimits a YAML block and `+++` a TOML block. Keys that are not known
+/// here are kept in `extra` so themes can use them.
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct FrontMatter {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<Ulid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_date", skip_serializing_if = "Option::is_none")]
+    pub date: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub categories: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub draft: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<Ulid>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FrontMatterFormat {
+    Yaml,
+    Toml,
+}
+
+impl FrontMatterFormat {
+    fn delimiter(&self) -> &'static str {
+        match self {
+            FrontMatterFormat::Yaml => "---",
+            FrontMatterFormat::Toml => "+++",
+        }
+    }
+}
+
+/// Splits `content` into the raw front matter block and the remaining body.
+pub fn split(content: &str) -> Option<(FrontMatterFormat, &str, &str)> {
+    let format = [FrontMatterFormat::Yaml, FrontMatterFormat::Toml]
+        .into_iter()
+        .find(|f| content.lines().next().map(str::trim_end) == Some(f.delimiter()))?;
+    let start = content.find('\n')? + 1;
+    let mut offset = start;
+    for line in content[start..].split_inclusive('\n') {
+        if line.trim_end() == format.delimiter() {
+            return Some((format, &content[start..offset], &content[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+    None
+}
+
+impl FrontMatter {
+    pub fn parse(format: FrontMatterFormat, block: &str) -> Result<FrontMatter, Error> {
+        let value: Value = match format {
+            FrontMatterFormat::Yaml => match serde_yaml::from_str(block)? {
+                Value::Null => Value::Object(Map::new()),
+                value => value,
+            },
+            FrontMatterFormat::Toml => toml_to_json(toml::from_str(block)?),
+        };
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// The YAML block, delimiters included, written in front of saved articles.
+    pub fn to_block(&self) -> Result<String, Error> {
+        Ok(format!("---\n{}---\n", serde_yaml::to_string(self)?))
+    }
+}
+
+impl From<&Article> for FrontMatter {
+    fn from(article: &Article) -> Self {
+        FrontMatter {
+            id: Some(article.id()),
+            title: Some(article.title.clone()),
+            author: (!article.author.is_empty()).then(|| article.author.clone()),
+            date: Some(article.timestamp),
+            tags: article.tags.clone(),
+            categories: article.categories.clone(),
+            summary: article.summary.clone(),
+            draft: article.draft,
+            owner: article.owner,
+            extra: article.extra.clone(),
+        }
+    }
+}
+
+/// TOML datetimes deserialize as a private map, turn them into strings first.
+fn toml_to_json(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => Value::from(i),
+        toml::Value::Float(f) => Value::from(f),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(d) => Value::String(d.to_string()),
+        toml::Value::Array(a) => Value::Array(a.into_iter().map(toml_to_json).collect()),
+        toml::Value::Table(t) => {
+            Value::Object(t.into_iter().map(|(k, v)| (k, toml_to_json(v))).collect())
+        }
+    }
+}
+
+pub fn parse_date(date: &str) -> Option<DateTime<Utc>> {
+    let date = date.trim();
+    if let Ok(d) = DateTime::parse_from_rfc3339(date) {
+        return Some(d.with_timezone(&Utc));
+    }
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"] {
+        if let Ok(d) = NaiveDateTime::parse_from_str(date, format) {
+            return Some(d.and_utc());
+        }
+    }
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|d| d.and_utc())
+}
+
+fn deserialize_date<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<String>::deserialize(deserializer)? {
+        Some(date) => parse_date(&date)
+            .map(Some)
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid date: {}", date))),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_yaml_and_toml_blocks() {
+        let (format, block, body) = split("---\ntitle: Hello\n---\n# Body\n").unwrap();
+        assert_eq!(format, FrontMatterFormat::Yaml);
+        assert_eq!(block, "title: Hello\n");
+        assert_eq!(body, "# Body\n");
+
+        let (format, block, body) = split("+++\r\ntitle = \"Hello\"\r\n+++\r\nBody").unwrap();
+        assert_eq!(format, FrontMatterFormat::Toml);
+        assert_eq!(block, "title = \"Hello\"\r\n");
+        assert_eq!(body, "Body");
+    }
+
+    #[test]
+    fn leaves_content_without_a_closed_block_alone() {
+        assert!(split("# Body\n---\n").is_none());
+        assert!(split("---\ntitle: Hello\n").is_none());
+        assert!(split("+++\ntitle: Hello\n---\n").is_none());
+    }
+
+    #[test]
+    fn parses_yaml() {
+        let block = "id: 01J1Z8Q6X3M2A0B9C8D7E6F5G4\ntitle: Hello\ndate: 2024-07-01\n\
+                     tags: [rust, web]\ndraft: true\nhero: /hero.png\n";
+        let front_matter = FrontMatter::parse(FrontMatterFormat::Yaml, block).unwrap();
+        assert_eq!(front_matter.id.unwrap().to_string(), "01J1Z8Q6X3M2A0B9C8D7E6F5G4");
+        assert_eq!(front_matter.title.as_deref(), Some("Hello"));
+        assert_eq!(front_matter.date, parse_date("2024-07-01"));
+        assert_eq!(front_matter.tags, ["rust", "web"]);
+        assert!(front_matter.draft);
+        assert_eq!(front_matter.extra["hero"], "/hero.png");
+
+        let empty = FrontMatter::parse(FrontMatterFormat::Yaml, "").unwrap();
+        assert!(empty.title.is_none() && !empty.draft);
+    }
+
+    #[test]
+    fn parses_toml_with_native_dates() {
+        let block = "title = \"Hello\"\ndate = 2024-07-01T12:30:00Z\ncategories = [\"notes\"]\n\
+                     [extra_table]\nkey = 1\n";
+        let front_matter = FrontMatter::parse(FrontMatterFormat::Toml, block).unwrap();
+        assert_eq!(front_matter.date, parse_date("2024-07-01 12:30:00"));
+        assert_eq!(front_matter.categories, ["notes"]);
+        assert_eq!(front_matter.extra["extra_table"]["key"], 1);
+    }
+
+    #[test]
+    fn rejects_invalid_front_matter() {
+        assert!(FrontMatter::parse(FrontMatterFormat::Yaml, "date: yesterday\n").is_err());
+        assert!(FrontMatter::parse(FrontMatterFormat::Yaml, "tags: rust\n").is_err());
+        assert!(FrontMatter::parse(FrontMatterFormat::Toml, "title = \n").is_err());
+    }
+
+    #[test]
+    fn parses_dates_with_and_without_time() {
+        let noon = DateTime::parse_from_rfc3339("2024-07-01T12:00:00Z").unwrap().to_utc();
+        assert_eq!(parse_date("2024-07-01T14:00:00+02:00"), Some(noon));
+        assert_eq!(parse_date("2024-07-01 12:00:00"), Some(noon));
+        assert_eq!(parse_date("2024-07-01T12:00:00"), Some(noon));
+        assert_eq!(parse_date(" 2024-07-01 12:00 "), Some(noon));
+        assert_eq!(parse_date("2024-07-01"), Some(noon - chrono::Duration::hours(12)));
+        assert_eq!(parse_date("01/07/2024"), None);
+        assert_eq!(parse_date("2024-13-01"), None);
+    }
+
+    #[test]
+    fn writes_a_block_that_parses_back() {
+        let front_matter = FrontMatter {
+            title: Some("Hello".to_string()),
+            date: parse_date("2024-07-01"),
+            tags: vec!["rust".to_string()],
+            ..FrontMatter::default()
+        };
+        let block = front_matter.to_block().unwrap();
+        let (format, block, body) = split(&block).unwrap();
+        assert_eq!((format, body), (FrontMatterFormat::Yaml, ""));
+        let parsed = FrontMatter::parse(format, block).unwrap();
+        assert_eq!(parsed.title, front_matter.title);
+        assert_eq!(parsed.date, front_matter.date);
+        assert_eq!(parsed.tags, front_matter.tags);
+    }
+}
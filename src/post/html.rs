@@ -1,11 +1,13 @@
 use super::Join;
-use ammonia::{clean, is_html};
+use ammonia::{clean, is_html, Builder};
 use minify_html::minify;
 use rocket::response::content::RawHtml;
-use crate::Error;
+use serde::{Deserialize, Serialize};
+use crate::{Error, StringCutter};
 use std::fmt::Display;
 use std::{fmt, io};
-#[derive(Default, Clone, Debug)]
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct Html(String);
 
 impl Display for Html {
@@ -75,10 +77,18 @@ impl Html {
             Err(e) => Err(e.to_string().into()),
         }
     }
-    pub fn validate(&self) -> bool {
-        is_html(&self.0)
+    /// Fails on content without a single HTML element, like plain text.
+    pub fn validate(&self) -> Result<(), Error> {
+        match is_html(&self.0) {
+            true => Ok(()),
+            false => Err(format!("No HTML element in {:?}", self.0.cut_to_length(40)).into()),
+        }
     }
     pub fn sanitize(&self) -> Html {
         Html(clean(&self.0))
     }
+    /// Text content with every tag stripped.
+    pub fn text(&self) -> String {
+        Builder::empty().clean(&self.0).to_string()
+    }
 }
@@ -1,11 +1,14 @@
+use super::front_matter::{self, FrontMatter};
 use super::{Html, Join};
 use crate::StringCutter;
 use async_trait::async_trait;
 use markdown::{mdast, to_html_with_options};
+use serde::{Deserialize, Serialize};
 use crate::Error;
 use std::fmt::Display;
 
-#[derive(Default, Clone, Debug)]
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct Markdown(String);
 impl Display for Markdown {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -27,27 +30,57 @@ impl Markdown {
     pub fn new(content: String) -> Markdown {
         Markdown(content)
     }
+    /// Parsed front matter block, `None` if the article has none.
+    pub fn front_matter(&self) -> Result<Option<FrontMatter>, Error> {
+        match front_matter::split(&self.0) {
+            Some((format, block, _)) => FrontMatter::parse(format, block).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// The Markdown content without its front matter block.
+    pub fn body(&self) -> &str {
+        match front_matter::split(&self.0) {
+            Some((_, _, body)) => body,
+            None => &self.0,
+        }
+    }
+
     pub fn to_html(&self, type_: MarkdownType) -> Result<Html, Error> {
         match type_ {
             MarkdownType::Common => {
-                match to_html_with_options(&self.0, &markdown::Options::default()) {
+                match to_html_with_options(self.body(), &markdown::Options::default()) {
                     Ok(html) => Ok(Html::from(html)),
                     Err(e) => Err(e.to_string().into()),
                 }
             }
-            MarkdownType::Gfm => match to_html_with_options(&self.0, &markdown::Options::gfm()) {
+            MarkdownType::Gfm => match to_html_with_options(self.body(), &markdown::Options::gfm()) {
                 Ok(html) => Ok(Html::from(html)),
                 Err(e) => Err(e.to_string().into()),
             },
         }
     }
+
+    /// Text of the first level one heading, if any.
+    pub fn title(&self) -> Option<String> {
+        let ast = markdown::to_mdast(self.body(), &Default::default()).ok()?;
+        ast.children()?.iter().find_map(|n| match n {
+            mdast::Node::Heading(h) if h.depth == 1 => h.children.iter().find_map(|n| match n {
+                mdast::Node::Text(t) => Some(t.value.to_string()),
+                _ => None,
+            }),
+            _ => None,
+        })
+    }
 }
 
-#[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Default, Serialize)]
 pub struct ArticlePrev {
-    title: String,
-    body: String,
+    pub title: String,
+    pub body: String,
+    pub url: String,
+    pub author: String,
+    pub date: String,
 }
 
 #[async_trait]
@@ -58,7 +91,7 @@ pub trait PreviewArticle {
 #[async_trait]
 impl PreviewArticle for Markdown {
     async fn preview(&self) -> Result<ArticlePrev, Error> {
-        let ast = match markdown::to_mdast(&self.to_string(), &Default::default()) {
+        let ast = match markdown::to_mdast(self.body(), &Default::default()) {
             Ok(a) => a,
             Err(e) => return Err(e.to_string().into()),
         };
@@ -92,6 +125,7 @@ impl PreviewArticle for Markdown {
                 },
                 None => return Err("Failed to parse article".to_string().into()),
             },
+            ..Default::default()
         };
         Ok(art)
     }
@@ -1,9 +1,15 @@
 pub mod article;
+pub mod front_matter;
 mod html;
 mod markdown;
+pub mod project;
+pub mod repository;
+pub mod taxonomy;
 
+pub use front_matter::FrontMatter;
 pub use html::Html;
 pub use markdown::*;
+pub use repository::ArticleRepository;
 
 #[allow(dead_code)]
 pub trait Join<T> {
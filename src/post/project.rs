@@ -0,0 +1,88 @@
+use super::article::Article;
+use super::PreviewArticle;
+use crate::Error;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Section of the articles describing projects, `articles/projects/<slug>.md`.
+pub const PROJECTS_SECTION: &str = "projects";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProjectStatus {
+    Planned,
+    #[default]
+    Active,
+    Maintained,
+    Completed,
+    Archived,
+}
+
+/// Project fields of the front matter, next to the usual article fields.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct ProjectFrontMatter {
+    repo: Option<String>,
+    /// Link to the running project, if it has one.
+    website: Option<String>,
+    tech: Vec<String>,
+    status: ProjectStatus,
+    /// Image urls, the first one is the card image.
+    screenshots: Vec<String>,
+    featured: bool,
+}
+
+/// A portfolio entry, read from an article of [`PROJECTS_SECTION`].
+///
+/// The article's title is the name, its summary or preview the description and
+/// its body the detail page.
+#[derive(Clone, Debug, Serialize)]
+pub struct Project {
+    pub slug: String,
+    pub name: String,
+    pub description: String,
+    pub url: String,
+    pub date: String,
+    pub repo: Option<String>,
+    pub website: Option<String>,
+    pub tech: Vec<String>,
+    pub status: ProjectStatus,
+    pub screenshots: Vec<String>,
+    pub featured: bool,
+    #[serde(skip)]
+    pub timestamp: DateTime<Utc>,
+}
+
+impl Project {
+    pub async fn from_article(article: &Article) -> Result<Project, Error> {
+        let front_matter: ProjectFrontMatter =
+            serde_json::from_value(Value::Object(article.extra.clone())).map_err(|e| {
+                format!("Invalid project front matter in {}: {}", article.url(), e)
+            })?;
+        let preview = article.preview().await?;
+        Ok(Project {
+            slug: article.slug.clone(),
+            name: article.title.clone(),
+            description: preview.body,
+            url: article.url(),
+            date: preview.date,
+            repo: front_matter.repo,
+            website: front_matter.website,
+            tech: front_matter.tech,
+            status: front_matter.status,
+            screenshots: front_matter.screenshots,
+            featured: front_matter.featured,
+            timestamp: article.timestamp,
+        })
+    }
+}
+
+/// Featured projects first, then the newest.
+pub fn sort_projects(projects: &mut [Project]) {
+    projects.sort_by(|a, b| {
+        b.featured
+            .cmp(&a.featured)
+            .then_with(|| b.timestamp.cmp(&a.timestamp))
+    });
+}
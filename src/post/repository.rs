@@ -0,0 +1,419 @@
+use super::article::{Article, Content};
+use super::taxonomy::{Taxonomy, TaxonomyIndex, Term};
+use super::front_matter;
+use super::{FrontMatter, Html, Markdown};
+use crate::db::{Data, Store};
+use crate::{Error, SnakeToTitleCase, Slugify, StableHash};
+use chrono::{DateTime, Utc};
+use std::cmp::Reverse;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::fs::{create_dir_all, read_dir, read_to_string, remove_file, write};
+use ulid::Ulid;
+
+/// In-memory index of the articles found under `articles/<section>/*.md`.
+///
+/// Articles are stored by [`Ulid`] with a secondary `"<section>/<slug>"` index
+/// so routes can resolve the path segments they receive.
+#[derive(Clone)]
+pub struct ArticleRepository {
+    dir: Arc<Path>,
+    articles: Data<Ulid, Article>,
+    slugs: Data<String, Ulid>,
+    taxonomy: TaxonomyIndex,
+}
+
+fn slug_key(section: &str, slug: &str) -> String {
+    format!("{}/{}", section, slug)
+}
+
+/// Id of an article without one in its front matter, derived from its path
+/// alone so it stays the same across edits and restarts.
+fn file_id(key: &str) -> Ulid {
+    Ulid::from_parts(0, key.stable_hash() as u128)
+}
+
+impl ArticleRepository {
+    pub async fn index(dir: Arc<Path>) -> Result<Self, Error> {
+        let repository = Self {
+            dir,
+            articles: Data::new(false),
+            slugs: Data::new(false),
+            taxonomy: TaxonomyIndex::new(),
+        };
+        if !tokio::fs::try_exists(&repository.dir).await? {
+            return Ok(repository);
+        }
+        let mut sections = read_dir(&repository.dir).await?;
+        while let Some(section) = sections.next_entry().await? {
+            if !section.file_type().await?.is_dir() {
+                continue;
+            }
+            let section_name = section.file_name().to_string_lossy().to_string();
+            let mut files = read_dir(section.path()).await?;
+            while let Some(file) = files.next_entry().await? {
+                let path = file.path();
+                if !path.extension().is_some_and(|e| e == "md" || e == "html") {
+                    continue;
+                }
+                let article = repository.read_article(&section_name, &path).await?;
+                repository.insert(article).await?;
+            }
+        }
+        Ok(repository)
+    }
+
+    async fn read_article(&self, section: &str, path: &Path) -> Result<Article, Error> {
+        let slug = path
+            .file_stem()
+            .ok_or_else(|| format!("Invalid article path: {}", path.display()))?
+            .to_string_lossy()
+            .to_string();
+        let source = read_to_string(path).await?;
+        let (front_matter, body) = match front_matter::split(&source) {
+            Some((format, block, body)) => (
+                FrontMatter::parse(format, block)
+                    .map_err(|e| format!("Invalid front matter in {}: {}", path.display(), e))?,
+                body,
+            ),
+            None => (FrontMatter::default(), source.as_str()),
+        };
+        let content = match path.extension().is_some_and(|e| e == "html") {
+            true => Content::Html(Html::new(body.to_string())),
+            false => Content::Markdown(Markdown::new(body.to_string())),
+        };
+        let modified: DateTime<Utc> = tokio::fs::metadata(path).await?.modified()?.into();
+        let timestamp = front_matter.date.unwrap_or(modified);
+        let title = front_matter
+            .title
+            .or_else(|| match &content {
+                Content::Markdown(markdown) => markdown.title(),
+                Content::Html(_) => None,
+            })
+            .unwrap_or_else(|| slug.snake_to_title_case());
+        let id = front_matter
+            .id
+            .unwrap_or_else(|| file_id(&slug_key(section, &slug)));
+        let mut article = Article::new(
+            id,
+            slug,
+            section.to_string(),
+            title,
+            front_matter.author.unwrap_or_default(),
+            timestamp,
+            content,
+        );
+        article.summary = front_matter.summary;
+        article.tags = front_matter.tags;
+        article.categories = front_matter.categories;
+        article.draft = front_matter.draft;
+        article.owner = front_matter.owner;
+        article.extra = front_matter.extra;
+        Ok(article)
+    }
+
+    fn path_of(&self, article: &Article) -> PathBuf {
+        let extension = match article.content {
+            Content::Markdown(_) => "md",
+            Content::Html(_) => "html",
+        };
+        self.dir
+            .join(&article.section)
+            .join(&article.slug)
+            .with_extension(extension)
+    }
+
+    /// Writes `article` to `<section>/<slug>.<md|html>` with its front matter and
+    /// indexes it, replacing the previous file if the article moved.
+    pub async fn save(&self, article: Article) -> Result<(), Error> {
+        if !article.section.is_slug() || !article.slug.is_slug() {
+            return Err(format!("Invalid article path: {}", slug_key(&article.section, &article.slug)).into());
+        }
+        let path = self.path_of(&article);
+        if let Some(old) = self.get(&article.id()).await? {
+            self.forget(&old).await?;
+            let old_path = self.path_of(&old);
+            if old_path != path && tokio::fs::try_exists(&old_path).await? {
+                remove_file(old_path).await?;
+            }
+        }
+        let body = match &article.content {
+            Content::Markdown(markdown) => markdown.body().to_string(),
+            Content::Html(html) => html.to_string(),
+        };
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent).await?;
+        }
+        write(&path, FrontMatter::from(&article).to_block()? + &body).await?;
+        self.insert(article).await
+    }
+
+    /// Deletes the article and its file.
+    pub async fn remove(&self, id: &Ulid) -> Result<Option<Article>, Error> {
+        let Some(article) = self.get(id).await? else {
+            return Ok(None);
+        };
+        self.forget(&article).await?;
+        let path = self.path_of(&article);
+        if tokio::fs::try_exists(&path).await? {
+            remove_file(path).await?;
+        }
+        Ok(Some(article))
+    }
+
+    /// Reads `path`, a file of `section`, again after it changed on disk and
+    /// forgets its article when the file is gone.
+    ///
+    /// Returns the previous and the new version of the article, `None` for
+    /// one that does not exist, so the pages showing them can be dropped.
+    pub async fn reload_file(
+        &self,
+        section: &str,
+        path: &Path,
+    ) -> Result<(Option<Article>, Option<Article>), Error> {
+        let slug = path
+            .file_stem()
+            .ok_or_else(|| format!("Invalid article path: {}", path.display()))?
+            .to_string_lossy()
+            .to_string();
+        let previous = self.by_slug(section, &slug).await?;
+        if tokio::fs::try_exists(path).await? {
+            let article = self.read_article(section, path).await?;
+            self.insert(article.clone()).await?;
+            return Ok((previous, Some(article)));
+        }
+        if let Some(old) = &previous {
+            self.forget(old).await?;
+        }
+        Ok((previous, None))
+    }
+
+    async fn forget(&self, article: &Article) -> Result<(), Error> {
+        let key = slug_key(&article.section, &article.slug);
+        if self.slugs.get(&key).await? == Some(article.id()) {
+            self.slugs.delete(&key).await?;
+        }
+        self.taxonomy.remove(article).await?;
+        self.articles.delete(&article.id()).await
+    }
+
+    pub async fn insert(&self, article: Article) -> Result<(), Error> {
+        let key = slug_key(&article.section, &article.slug);
+        if let Some(old) = self.articles.get(&article.id()).await? {
+            self.forget(&old).await?;
+        }
+        if let Some(old) = self.slugs.get(&key).await? {
+            if let Some(old) = self.articles.get(&old).await? {
+                self.forget(&old).await?;
+            }
+        }
+        self.taxonomy.add(&article).await?;
+        self.slugs.insert(key, article.id()).await?;
+        self.articles.insert(article.id(), article).await
+    }
+
+    pub async fn get(&self, id: &Ulid) -> Result<Option<Article>, Error> {
+        self.articles.get(id).await
+    }
+
+    pub async fn by_slug(&self, section: &str, slug: &str) -> Result<Option<Article>, Error> {
+        match self.slugs.get(&slug_key(section, slug)).await? {
+            Some(id) => self.articles.get(&id).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Every article of every section, newest first.
+    pub async fn all(&self) -> Result<Vec<Article>, Error> {
+        let mut articles = self.articles.values().await?;
+        articles.sort_by_key(|a| Reverse(a.timestamp));
+        Ok(articles)
+    }
+
+    /// Articles of `section`, newest first.
+    pub async fn list(&self, section: &str) -> Result<Vec<Article>, Error> {
+        let mut articles: Vec<Article> = self
+            .articles
+            .values()
+            .await?
+            .into_iter()
+            .filter(|a| a.section == section)
+            .collect();
+        articles.sort_by_key(|a| Reverse(a.timestamp));
+        Ok(articles)
+    }
+
+    /// Non-draft articles of `section`, newest first.
+    pub async fn published(&self, section: &str) -> Result<Vec<Article>, Error> {
+        let mut articles = self.list(section).await?;
+        articles.retain(|a| !a.draft);
+        Ok(articles)
+    }
+
+    /// Display name of `term` and its published articles, newest first.
+    pub async fn by_term(
+        &self,
+        taxonomy: Taxonomy,
+        term: &str,
+    ) -> Result<Option<(String, Vec<Article>)>, Error> {
+        let Some((name, ids)) = self.taxonomy.get(taxonomy, term).await? else {
+            return Ok(None);
+        };
+        let mut articles = Vec::new();
+        for id in ids {
+            if let Some(article) = self.articles.get(&id).await? {
+                articles.push(article);
+            }
+        }
+        articles.sort_by_key(|a| Reverse(a.timestamp));
+        Ok(Some((name, articles)))
+    }
+
+    pub async fn terms(&self, taxonomy: Taxonomy) -> Result<Vec<Term>, Error> {
+        self.taxonomy.terms(taxonomy).await
+    }
+
+    /// Number of non-draft articles of `section`, as the listings show them.
+    pub async fn count(&self, section: &str) -> Result<usize, Error> {
+        Ok(self
+            .articles
+            .values()
+            .await?
+            .iter()
+            .filter(|a| a.section == section && !a.draft)
+            .count())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn articles_dir(files: &[(&str, &str)]) -> Arc<Path> {
+        let dir = std::env::temp_dir().join(format!("myweb-articles-{}", Ulid::new()));
+        for (path, source) in files {
+            let path = dir.join(path);
+            create_dir_all(path.parent().unwrap()).await.unwrap();
+            write(path, source).await.unwrap();
+        }
+        dir.into()
+    }
+
+    #[tokio::test]
+    async fn indexes_articles_by_section_and_slug() {
+        let dir = articles_dir(&[
+            ("blog/first.md", "---\ndate: 2024-07-01\n---\n# First article\n\nBody"),
+            ("blog/second.html", "---\ntitle: Second\ndate: 2024-07-02\n---\n<p>Body</p>"),
+            ("blog/notes.txt", "not an article"),
+            ("projects/first.md", "# A project"),
+        ])
+        .await;
+        let repository = ArticleRepository::index(dir).await.unwrap();
+
+        let first = repository.by_slug("blog", "first").await.unwrap().unwrap();
+        assert_eq!(first.title, "First article");
+        assert_eq!(repository.get(&first.id()).await.unwrap().unwrap().slug, "first");
+        let second = repository.by_slug("blog", "second").await.unwrap().unwrap();
+        assert!(matches!(second.content, Content::Html(_)));
+        assert!(repository.by_slug("blog", "notes").await.unwrap().is_none());
+        assert_eq!(repository.by_slug("projects", "first").await.unwrap().unwrap().title, "A project");
+
+        let slugs: Vec<String> = repository.list("blog").await.unwrap().into_iter().map(|a| a.slug).collect();
+        assert_eq!(slugs, ["second", "first"]);
+        assert_eq!(repository.all().await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn leaves_drafts_out_of_the_published_articles() {
+        let dir = articles_dir(&[
+            ("blog/published.md", "# Published"),
+            ("blog/draft.md", "---\ndraft: true\n---\n# Draft"),
+        ])
+        .await;
+        let repository = ArticleRepository::index(dir).await.unwrap();
+
+        assert_eq!(repository.list("blog").await.unwrap().len(), 2);
+        let published = repository.published("blog").await.unwrap();
+        assert_eq!(published.len(), 1);
+        assert_eq!(published[0].slug, "published");
+        assert_eq!(repository.count("blog").await.unwrap(), 1);
+        assert_eq!(repository.count("projects").await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn keeps_the_id_of_an_edited_article_without_one() {
+        let dir = articles_dir(&[("blog/first.md", "# First")]).await;
+        let repository = ArticleRepository::index(dir.clone()).await.unwrap();
+        let id = repository.by_slug("blog", "first").await.unwrap().unwrap().id();
+
+        let path = dir.join("blog/first.md");
+        write(&path, "# First, edited").await.unwrap();
+        let (previous, current) = repository.reload_file("blog", &path).await.unwrap();
+        assert_eq!(previous.unwrap().id(), id);
+        assert_eq!(current.unwrap().id(), id);
+
+        let reindexed = ArticleRepository::index(dir).await.unwrap();
+        assert_eq!(reindexed.by_slug("blog", "first").await.unwrap().unwrap().id(), id);
+        assert_eq!(reindexed.get(&id).await.unwrap().unwrap().title, "First, edited");
+    }
+
+    #[tokio::test]
+    async fn saves_and_removes_article_files() {
+        let dir = articles_dir(&[]).await;
+        let repository = ArticleRepository::index(dir.clone()).await.unwrap();
+        let article = Article::new(
+            Ulid::new(),
+            "saved".to_string(),
+            "blog".to_string(),
+            "Saved".to_string(),
+            String::new(),
+            Utc::now(),
+            Content::Markdown(Markdown::new("Body".to_string())),
+        );
+        repository.save(article.clone()).await.unwrap();
+        let reindexed = ArticleRepository::index(dir.clone()).await.unwrap();
+        assert_eq!(reindexed.by_slug("blog", "saved").await.unwrap().unwrap().id(), article.id());
+
+        let mut moved = article.clone();
+        moved.slug = "moved".to_string();
+        repository.save(moved).await.unwrap();
+        assert!(repository.by_slug("blog", "saved").await.unwrap().is_none());
+        assert!(!tokio::fs::try_exists(dir.join("blog/saved.md")).await.unwrap());
+
+        repository.remove(&article.id()).await.unwrap();
+        assert!(repository.get(&article.id()).await.unwrap().is_none());
+        assert!(!tokio::fs::try_exists(dir.join("blog/moved.md")).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn lists_the_published_articles_of_a_term() {
+        let dir = articles_dir(&[
+            ("blog/old.md", "---\ndate: 2024-07-01\ntags: [Rust Lang]\ncategories: [notes]\n---\n# Old"),
+            ("blog/new.md", "---\ndate: 2024-07-02\ntags: [Rust Lang, web]\n---\n# New"),
+            ("blog/draft.md", "---\ntags: [rust lang, drafts]\ndraft: true\n---\n# Draft"),
+        ])
+        .await;
+        let repository = ArticleRepository::index(dir.clone()).await.unwrap();
+
+        let (name, articles) = repository.by_term(Taxonomy::Tags, "rust-lang").await.unwrap().unwrap();
+        assert_eq!(name, "Rust Lang");
+        let slugs: Vec<&str> = articles.iter().map(|a| a.slug.as_str()).collect();
+        assert_eq!(slugs, ["new", "old"]);
+        assert!(repository.by_term(Taxonomy::Tags, "Rust Lang").await.unwrap().is_some());
+        assert!(repository.by_term(Taxonomy::Tags, "drafts").await.unwrap().is_none());
+        assert!(repository.by_term(Taxonomy::Tags, "notes").await.unwrap().is_none());
+        assert_eq!(repository.by_term(Taxonomy::Categories, "notes").await.unwrap().unwrap().1.len(), 1);
+
+        let terms = repository.terms(Taxonomy::Tags).await.unwrap();
+        let counts: Vec<(&str, usize, usize)> =
+            terms.iter().map(|t| (t.slug.as_str(), t.count, t.weight)).collect();
+        assert_eq!(counts[0], ("rust-lang", 2, 5));
+        assert_eq!(counts.len(), 2);
+
+        let path = dir.join("blog/old.md");
+        write(&path, "---\ndate: 2024-07-01\n---\n# Old").await.unwrap();
+        repository.reload_file("blog", &path).await.unwrap();
+        assert_eq!(repository.by_term(Taxonomy::Tags, "rust-lang").await.unwrap().unwrap().1.len(), 1);
+        assert!(repository.by_term(Taxonomy::Categories, "notes").await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,149 @@
+use super::article::Article;
+use crate::db::{Data, Store};
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use ulid::Ulid;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Taxonomy {
+    Tags,
+    Categories,
+}
+
+impl Taxonomy {
+    pub fn path(&self) -> &'static str {
+        match self {
+            Taxonomy::Tags => "tags",
+            Taxonomy::Categories => "categories",
+        }
+    }
+
+    pub fn terms<'a>(&self, article: &'a Article) -> &'a [String] {
+        match self {
+            Taxonomy::Tags => &article.tags,
+            Taxonomy::Categories => &article.categories,
+        }
+    }
+}
+
+/// Lowercased, dash separated form of a term used in urls and as index key.
+pub fn term_slug(term: &str) -> String {
+    term.split_whitespace()
+        .collect::<Vec<_>>()
+        .join("-")
+        .to_lowercase()
+}
+
+#[derive(Clone, Debug)]
+struct TermEntry {
+    name: String,
+    articles: Vec<Ulid>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Term {
+    pub name: String,
+    pub slug: String,
+    pub url: String,
+    pub count: usize,
+    /// 1 to 5, relative to the most used term, for tag clouds.
+    pub weight: usize,
+}
+
+/// Term to article index for tags and categories of published articles.
+#[derive(Clone)]
+pub struct TaxonomyIndex {
+    tags: Data<String, TermEntry>,
+    categories: Data<String, TermEntry>,
+}
+
+impl Default for TaxonomyIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TaxonomyIndex {
+    pub fn new() -> Self {
+        Self {
+            tags: Data::new(false),
+            categories: Data::new(false),
+        }
+    }
+
+    fn data(&self, taxonomy: Taxonomy) -> &Data<String, TermEntry> {
+        match taxonomy {
+            Taxonomy::Tags => &self.tags,
+            Taxonomy::Categories => &self.categories,
+        }
+    }
+
+    pub async fn add(&self, article: &Article) -> Result<(), Error> {
+        if article.draft {
+            return Ok(());
+        }
+        for taxonomy in [Taxonomy::Tags, Taxonomy::Categories] {
+            let data = self.data(taxonomy);
+            for term in taxonomy.terms(article) {
+                let slug = term_slug(term);
+                let mut entry = data.get(&slug).await?.unwrap_or_else(|| TermEntry {
+                    name: term.clone(),
+                    articles: Vec::new(),
+                });
+                if !entry.articles.contains(&article.id()) {
+                    entry.articles.push(article.id());
+                }
+                data.insert(slug, entry).await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn remove(&self, article: &Article) -> Result<(), Error> {
+        for taxonomy in [Taxonomy::Tags, Taxonomy::Categories] {
+            let data = self.data(taxonomy);
+            for term in taxonomy.terms(article) {
+                let slug = term_slug(term);
+                if let Some(mut entry) = data.get(&slug).await? {
+                    entry.articles.retain(|id| *id != article.id());
+                    match entry.articles.is_empty() {
+                        true => data.delete(&slug).await?,
+                        false => data.insert(slug, entry).await?,
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Display name and article ids of a term.
+    pub async fn get(&self, taxonomy: Taxonomy, term: &str) -> Result<Option<(String, Vec<Ulid>)>, Error> {
+        Ok(self
+            .data(taxonomy)
+            .get(&term_slug(term))
+            .await?
+            .map(|e| (e.name, e.articles)))
+    }
+
+    /// Every term of `taxonomy`, most used first.
+    pub async fn terms(&self, taxonomy: Taxonomy) -> Result<Vec<Term>, Error> {
+        let entries = self.data(taxonomy).values().await?;
+        let max = entries.iter().map(|e| e.articles.len()).max().unwrap_or(1);
+        let mut terms: Vec<Term> = entries
+            .into_iter()
+            .map(|e| {
+                let slug = term_slug(&e.name);
+                Term {
+                    url: format!("/{}/{}", taxonomy.path(), slug),
+                    slug,
+                    count: e.articles.len(),
+                    weight: 1 + (e.articles.len() - 1) * 4 / max.saturating_sub(1).max(1),
+                    name: e.name,
+                }
+            })
+            .collect();
+        terms.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+        Ok(terms)
+    }
+}
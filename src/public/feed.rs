@@ -0,0 +1,204 @@
+use crate::config::Config;
+use crate::post::article::{Article, Content};
+use crate::post::{ArticleRepository, MarkdownType, PreviewArticle};
+use crate::settings::SiteSettings;
+use crate::Error;
+use atom_syndication as atom;
+use chrono::{DateTime, Utc};
+use rocket::http::{ContentType, Status};
+use rocket::{get, State};
+use std::collections::BTreeMap;
+
+/// A single feed entry, shared by the Atom and RSS writers.
+struct FeedItem {
+    title: String,
+    url: String,
+    author: String,
+    timestamp: DateTime<Utc>,
+    summary: String,
+    content: Option<String>,
+}
+
+impl FeedItem {
+    async fn new(article: &Article, config: &Config, site: &SiteSettings) -> Result<FeedItem, Error> {
+        let content = match config.feed_full_content {
+            true => Some(
+                match &article.content {
+                    Content::Markdown(markdown) => markdown.to_html(MarkdownType::Gfm)?,
+                    Content::Html(html) => html.clone(),
+                }
+                .sanitize()
+                .to_string(),
+            ),
+            false => None,
+        };
+        Ok(FeedItem {
+            title: article.title.clone(),
+            url: format!("{}{}", site.base_url, article.url()),
+            author: match article.author.is_empty() {
+                true => site.site_name.clone(),
+                false => article.author.clone(),
+            },
+            timestamp: article.timestamp,
+            summary: article.preview().await?.body,
+            content,
+        })
+    }
+}
+
+async fn feed_items(
+    articles: &ArticleRepository,
+    config: &Config,
+    site: &SiteSettings,
+    section: &str,
+) -> Result<Vec<FeedItem>, Error> {
+    let mut items = Vec::new();
+    for article in articles.published(section).await?.iter().take(config.feed_items) {
+        items.push(FeedItem::new(article, config, site).await?);
+    }
+    Ok(items)
+}
+
+fn atom_feed(items: &[FeedItem], site: &SiteSettings, section: &str) -> String {
+    let section_url = format!("{}/{}", site.base_url, section);
+    let updated = items.iter().map(|i| i.timestamp).max().unwrap_or_else(Utc::now);
+    let entries = items
+        .iter()
+        .map(|item| atom::Entry {
+            title: item.title.clone().into(),
+            id: item.url.clone(),
+            updated: item.timestamp.fixed_offset(),
+            published: Some(item.timestamp.fixed_offset()),
+            authors: vec![atom::Person {
+                name: item.author.clone(),
+                ..Default::default()
+            }],
+            links: vec![atom::Link {
+                href: item.url.clone(),
+                rel: "alternate".to_string(),
+                ..Default::default()
+            }],
+            summary: Some(item.summary.clone().into()),
+            content: item.content.clone().map(|html| atom::Content {
+                value: Some(html),
+                content_type: Some("html".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+        .collect();
+    atom::Feed {
+        title: site.site_name.clone().into(),
+        id: section_url.clone(),
+        updated: updated.fixed_offset(),
+        links: vec![
+            atom::Link {
+                href: section_url,
+                rel: "alternate".to_string(),
+                ..Default::default()
+            },
+            atom::Link {
+                href: format!("{}/{}/feed.atom", site.base_url, section),
+                rel: "self".to_string(),
+                ..Default::default()
+            },
+        ],
+        entries,
+        ..Default::default()
+    }
+    .to_string()
+}
+
+fn rss_feed(items: &[FeedItem], config: &Config, site: &SiteSettings, section: &str) -> String {
+    let items = items
+        .iter()
+        .map(|item| rss::Item {
+            title: Some(item.title.clone()),
+            link: Some(item.url.clone()),
+            guid: Some(rss::Guid {
+                value: item.url.clone(),
+                permalink: true,
+            }),
+            author: Some(item.author.clone()),
+            pub_date: Some(item.timestamp.to_rfc2822()),
+            description: Some(item.summary.clone()),
+            content: item.content.clone(),
+            ..Default::default()
+        })
+        .collect();
+    let mut namespaces = BTreeMap::new();
+    if config.feed_full_content {
+        namespaces.insert(
+            "content".to_string(),
+            "http://purl.org/rss/1.0/modules/content/".to_string(),
+        );
+    }
+    rss::Channel {
+        title: site.site_name.clone(),
+        link: format!("{}/{}", site.base_url, section),
+        description: format!("Latest {} articles from {}", section, site.site_name),
+        namespaces,
+        items,
+        ..Default::default()
+    }
+    .to_string()
+}
+
+/// A feed that fails to build is a server error, its cause goes to the log
+/// rather than to the reader.
+fn feed_error(e: Error) -> Status {
+    rocket::error!("Failed to build a feed: {}", e);
+    Status::InternalServerError
+}
+
+#[get("/blog/feed.atom")]
+pub async fn blog_atom(
+    articles: &State<ArticleRepository>,
+    config: &State<Config>,
+    site: SiteSettings,
+) -> Result<(ContentType, String), Status> {
+    let items = feed_items(articles, config, &site, "blog")
+        .await
+        .map_err(feed_error)?;
+    Ok((
+        ContentType::new("application", "atom+xml"),
+        atom_feed(&items, &site, "blog"),
+    ))
+}
+
+#[get("/blog/feed.rss")]
+pub async fn blog_rss(
+    articles: &State<ArticleRepository>,
+    config: &State<Config>,
+    site: SiteSettings,
+) -> Result<(ContentType, String), Status> {
+    let items = feed_items(articles, config, &site, "blog")
+        .await
+        .map_err(feed_error)?;
+    Ok((
+        ContentType::new("application", "rss+xml"),
+        rss_feed(&items, config, &site, "blog"),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{client, get};
+    use crate::config::Config;
+    use rocket::http::{ContentType, Status};
+
+    #[tokio::test]
+    async fn serves_the_atom_and_rss_feeds() {
+        let articles = [("blog/first.md", "---\ndate: 2024-07-01\n---\n# First\n\nBody")];
+        let client = client(&articles, Config::default()).await;
+
+        let response = client.get("/blog/feed.atom").dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(ContentType::new("application", "atom+xml")));
+        assert!(response.into_string().await.unwrap().contains("<title>First</title>"));
+
+        let (status, rss) = get(&client, "/blog/feed.rss").await;
+        assert_eq!(status, Status::Ok);
+        assert!(rss.contains("<title>First</title>"));
+    }
+}
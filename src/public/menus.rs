@@ -3,30 +3,37 @@ use rocket::serde::{Deserialize, Serialize};
 use serde_json::json;
 use crate::json::Json;
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Menu {
     pub name: String,
     pub url: String,
+    /// Lower weights come first, entries of equal weight keep their order.
+    #[serde(default)]
+    pub weight: i32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<Menu>,
 }
-#[derive(Deserialize, Serialize, Debug)]
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Menus(Arc<[Menu]>);
 
+/// A [`Menu`] as templates see it, ordered and marked for the current path.
+#[derive(Serialize, Debug)]
+pub struct MenuItem<'a> {
+    pub name: &'a str,
+    pub url: &'a str,
+    pub external: bool,
+    pub active: bool,
+    pub children: Vec<MenuItem<'a>>,
+}
+
 impl Default for Menus {
     fn default() -> Self {
         Menus(
             vec![
-                Menu {
-                    name: "Blog".to_string(),
-                    url: "/blog".to_string(),
-                },
-                Menu {
-                    name: "Projects".to_string(),
-                    url: "/projects".to_string(),
-                },
-                Menu {
-                    name: "About".to_string(),
-                    url: "/about".to_string(),
-                },
+                Menu::new("Blog", "/blog"),
+                Menu::new("Projects", "/projects"),
+                Menu::new("About", "/about"),
             ]
                 .into(),
         )
@@ -45,8 +52,145 @@ impl TryFrom<Json> for Menus {
     }
 }
 
+impl Menu {
+    pub fn new(name: &str, url: &str) -> Self {
+        Menu {
+            name: name.to_string(),
+            url: url.to_string(),
+            weight: 0,
+            children: Vec::new(),
+        }
+    }
+
+    /// Links with a scheme or protocol relative links leave the site.
+    pub fn is_external(&self) -> bool {
+        self.url.contains("://") || self.url.starts_with("//") || self.url.starts_with("mailto:")
+    }
+
+    /// Whether `path` is this entry's page or one below it.
+    fn matches(&self, path: &str) -> bool {
+        if self.is_external() {
+            return false;
+        }
+        let url = self.url.trim_end_matches('/');
+        let path = path.trim_end_matches('/');
+        match url.is_empty() {
+            true => path.is_empty(),
+            false => path == url || path.strip_prefix(url).is_some_and(|rest| rest.starts_with('/')),
+        }
+    }
+
+    fn item(&self, path: &str) -> MenuItem<'_> {
+        let children = items(&self.children, path);
+        MenuItem {
+            name: &self.name,
+            url: &self.url,
+            external: self.is_external(),
+            active: self.matches(path) || children.iter().any(|c| c.active),
+            children,
+        }
+    }
+}
+
+fn items<'a>(menus: &'a [Menu], path: &str) -> Vec<MenuItem<'a>> {
+    let mut menus: Vec<&Menu> = menus.iter().collect();
+    menus.sort_by_key(|m| m.weight);
+    menus.into_iter().map(|m| m.item(path)).collect()
+}
+
 impl Menus {
-    pub fn get(&self) -> &Menu {
-        &self.0[0]
+    /// These menus followed by the entries of `extra` whose url is not in
+    /// them yet.
+    pub fn with(&self, extra: Vec<Menu>) -> Menus {
+        let mut menus = self.0.to_vec();
+        for menu in extra {
+            if !menus.iter().any(|m| m.url == menu.url) {
+                menus.push(menu);
+            }
+        }
+        Menus(menus.into())
+    }
+
+    /// Every entry, ordered by weight, with the ones leading to `path` active.
+    pub fn get(&self, path: &str) -> Vec<MenuItem<'_>> {
+        items(&self.0, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn menu(name: &str, url: &str, weight: i32, children: Vec<Menu>) -> Menu {
+        Menu {
+            weight,
+            children,
+            ..Menu::new(name, url)
+        }
+    }
+
+    fn names<'a>(items: &[MenuItem<'a>]) -> Vec<&'a str> {
+        items.iter().map(|i| i.name).collect()
+    }
+
+    #[test]
+    fn orders_entries_by_weight() {
+        let menus = Menus(
+            vec![
+                menu("About", "/about", 10, Vec::new()),
+                menu("Blog", "/blog", -1, Vec::new()),
+                menu("Projects", "/projects", 0, Vec::new()),
+                menu("Contact", "/contact", 0, Vec::new()),
+            ]
+            .into(),
+        );
+        // Equal weights keep the configured order.
+        assert_eq!(names(&menus.get("/")), ["Blog", "Projects", "Contact", "About"]);
+    }
+
+    #[test]
+    fn orders_children_by_weight() {
+        let children = vec![
+            menu("Second", "/docs/second", 2, Vec::new()),
+            menu("First", "/docs/first", 1, Vec::new()),
+        ];
+        let menus = Menus(vec![menu("Docs", "/docs", 0, children)].into());
+        assert_eq!(names(&menus.get("/")[0].children), ["First", "Second"]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn marks_the_entries_leading_to_the_path() {
+        let children = vec![menu("Rust", "/blog/tags/rust", 0, Vec::new())];
+        let menus = Menus(
+            vec![
+                menu("Home", "/", 0, Vec::new()),
+                menu("Blog", "/blog/", 0, children),
+                menu("Blogroll", "/blogroll", 0, Vec::new()),
+                menu("Source", "https://example.com/blog", 0, Vec::new()),
+            ]
+            .into(),
+        );
+        let active = |path| {
+            let items = menus.get(path);
+            let mut active: Vec<&str> = items.iter().filter(|i| i.active).map(|i| i.name).collect();
+            active.extend(items.iter().flat_map(|i| &i.children).filter(|c| c.active).map(|c| c.name));
+            active.into_iter().map(str::to_string).collect::<Vec<_>>()
+        };
+
+        assert_eq!(active("/"), ["Home"]);
+        assert_eq!(active("/blog"), ["Blog"]);
+        assert_eq!(active("/blog/post"), ["Blog"]);
+        assert_eq!(active("/blog/tags/rust/"), ["Blog", "Rust"]);
+        assert_eq!(active("/blogroll"), ["Blogroll"]);
+        assert!(active("/about").is_empty());
+    }
+
+    #[test]
+    fn adds_only_missing_entries() {
+        let menus = Menus::default().with(vec![
+            Menu::new("Blog again", "/blog"),
+            Menu::new("Uses", "/uses"),
+        ]);
+        assert_eq!(names(&menus.get("/")), ["Blog", "Projects", "About", "Uses"]);
+    }
+}
@@ -1,101 +1,141 @@
+mod feed;
 mod menus;
+mod pages;
+mod pagination;
+mod projects;
+mod taxonomy;
 
 use crate::config::Config;
-use crate::post::PreviewArticle;
-use crate::template::load_all_templates;
+use crate::db::Db;
+use crate::page::PageStore;
+use crate::post::article::Content;
+use crate::post::taxonomy::Taxonomy;
+use crate::post::{ArticleRepository, PreviewArticle};
+use crate::settings::{SettingsStore, SiteSettings};
 use crate::{
-    post::{Html, Markdown, MarkdownType},
-    render::{get_page, make_data, render, render_page, PageCache},
+    post::{Html, MarkdownType},
+    reload::LiveReload,
+    render::{get_page, make_data, render, render_page, CachedPage, Dependencies, Dependency, Page, PageCache},
     template::TemplatePool,
-    theme, Error, Result, SnakeToTitleCase, TitleCase,
+    theme::{ActiveTheme, ThemeRegistry},
+    Result,
 };
-use chrono::Duration;
 use figment::Figment;
 use handlebars::to_json;
+use serde_json::Value;
 use rocket::fairing::AdHoc;
 use rocket::fs::NamedFile;
+use rocket::response::stream::{Event, EventStream};
+use rocket::tokio::select;
+use rocket::tokio::sync::broadcast::error::RecvError;
+use rocket::Shutdown;
 use rocket::{
     get,
     response::{content::RawHtml, status::NotFound},
     routes, Build, Rocket, State,
 };
 use std::path::PathBuf;
-use std::sync::Arc;
-use tokio::fs::read_to_string;
 pub use menus::{Menu, Menus};
+use pagination::Pagination;
 type StdResult<T, E> = std::result::Result<T, E>;
-pub async fn launch(figment: &Figment) -> Result<Rocket<Build>> {
-    let theme_dir = &figment
-        .extract::<Config>()
-        .expect("Failed to extract config")
-        .theme_dir;
-
-    let theme = &figment
-        .extract::<Config>()
-        .expect("Failed to extract config")
-        .theme;
-
-    let template = load_all_templates(theme_dir, theme)
-        .await
-        .map(|templates| TemplatePool::from(false, templates))?;
-
-    let menus = Menus::default();
-
-    let page_cache: PageCache = PageCache::new(false);
+pub async fn launch(
+    figment: &Figment,
+    db: &Db,
+    articles: &ArticleRepository,
+    page_cache: &PageCache,
+    themes: &ThemeRegistry,
+    live_reload: Option<&LiveReload>,
+) -> Result<Rocket<Build>> {
+    let settings: SettingsStore = db.open("settings").await?;
+    let pages: PageStore = db.open("pages").await?;
 
     if cfg!(debug_assertions) {
-        let markdown = Markdown::from(read_to_string("articles/blog/ant_dilemma.md").await?);
-        let article_prev = &markdown.preview().await?;
-        let theme = theme::Theme::read(
-            &theme_dir
-                .join(theme.as_ref())
-                .join("meta")
-                .with_extension("toml"),
-        )
-        .await?;
-        println!("Preview: {:#?}", article_prev);
+        println!("Blog articles: {}", articles.count("blog").await?);
+        if let Some(Content::Markdown(markdown)) =
+            articles.list("blog").await?.first().map(|a| a.content.clone())
+        {
+            println!("Preview: {:#?}", markdown.preview().await?);
+        }
         // println!("Templates: {:#?}", template.read().await);
-        println!("Theme:\n{:#?}", theme);
+        println!("Theme:\n{:#?}", themes.active().await.manifest);
     }
 
     let rocket = rocket::custom(figment)
         .attach(AdHoc::config::<Config>())
-        .manage(template)
-        .manage(page_cache)
-        .manage(menus)
-        .mount("/", routes![index, static_files, blog, pages, not_found]);
+        .manage(db.clone())
+        .manage(articles.clone())
+        .manage(page_cache.clone())
+        .manage(settings)
+        .manage(pages)
+        .manage(themes.clone())
+        .mount(
+            "/",
+            routes![
+                index,
+                static_files,
+                blog_index,
+                feed::blog_atom,
+                feed::blog_rss,
+                taxonomy::tag,
+                taxonomy::category,
+                projects::projects,
+                projects::project,
+                blog,
+                pages::page,
+                not_found
+            ],
+        );
+
+    let rocket = match live_reload {
+        Some(live_reload) => rocket
+            .manage(live_reload.clone())
+            .mount("/", routes![live_reload_events]),
+        None => rocket,
+    };
 
     Ok(rocket)
 }
 
+/// Tells the page to reload whenever the watcher reloaded something.
+#[get("/live-reload")]
+pub fn live_reload_events(live_reload: &State<LiveReload>, mut shutdown: Shutdown) -> EventStream![] {
+    let mut reloads = live_reload.subscribe();
+    EventStream! {
+        loop {
+            select! {
+                reload = reloads.recv() => match reload {
+                    Ok(()) | Err(RecvError::Lagged(_)) => yield Event::data("reload"),
+                    Err(RecvError::Closed) => break,
+                },
+                _ = &mut shutdown => break,
+            }
+        }
+    }
+}
+
+/// Tags of published articles for the theme's tag cloud, empty on failure.
+pub async fn tag_cloud(articles: &ArticleRepository) -> Value {
+    to_json(articles.terms(Taxonomy::Tags).await.unwrap_or_default())
+}
+
 pub async fn make_404(
-    template_pool: &State<TemplatePool>,
+    theme: &ActiveTheme,
     message: &str,
-    menu: &State<Menus>,
+    site: &SiteSettings,
 ) -> Html {
     if message == "test" {
         #[allow(unused_variables)]
         let template = TemplatePool::new(false);
     }
-    let template_list = Box::new(vec![
-        ("default", "default"),
-        ("navbar", "navbar"),
-        ("overlay", "overlay"),
-        ("layout", "layout"),
-        ("article", "404"),
-    ]);
+    let components = [("parent", "layout"), ("article", "404")];
     let data_list = [
         ("parent".to_string(), to_json("layout")),
-        ("site_name".to_string(), to_json("ISAALULA")),
         ("page_title".to_string(), to_json("Not Found")),
         ("layout_min".to_string(), to_json(true)),
-        ("menus".to_string(), to_json(menu.get())),
-        ("default_theme".to_string(), to_json("mocha")),
-        ("secondary_theme".to_string(), to_json("latte")),
         ("message".to_string(), to_json(message)),
     ];
     let data = make_data(&data_list);
-    let html = render("default", template_pool, &template_list, data).await;
+    let html = render("default", theme, &components, site, data).await;
     match html {
         Ok(html) => html,
         Err(e) => Html::from(format!(
@@ -109,27 +149,28 @@ pub async fn make_404(
 }
 
 #[get("/404")]
-pub async fn not_found(template: &State<TemplatePool>, menus: &State<Menus>) -> RawHtml<String> {
-    RawHtml(make_404(template, "test fatal", menus).await.to_string())
+pub async fn not_found(theme: ActiveTheme, site: SiteSettings) -> RawHtml<String> {
+    RawHtml(make_404(&theme, "test fatal", &site).await.to_string())
 }
 
 #[get("/")]
 pub async fn index(
-    template_pool: &State<TemplatePool>,
+    theme: ActiveTheme,
     page_cache: &State<PageCache>,
-    menus: &State<Menus>,
-) -> StdResult<RawHtml<Arc<str>>, NotFound<RawHtml<String>>> {
+    site: SiteSettings,
+    articles: &State<ArticleRepository>,
+) -> StdResult<Page, NotFound<RawHtml<String>>> {
     let page = "index";
 
     if !cfg!(debug_assertions) {
-        let from_cache = get_page(page_cache, Duration::hours(1), page)
+        let from_cache = get_page(page_cache, &theme, page_cache.ttl("index"), page)
             .await
             .map_err(|e| e.to_string());
-        let cached: Option<Arc<str>> = match from_cache {
+        let cached: Option<CachedPage> = match from_cache {
             Ok(o) => o,
             Err(e) => {
                 return Err(NotFound(RawHtml(
-                    make_404(template_pool, &e.to_string(), menus)
+                    make_404(&theme, &e.to_string(), &site)
                         .await
                         .to_string(),
                 )))
@@ -137,210 +178,227 @@ pub async fn index(
         };
 
         if let Some(cached) = cached {
-            return Ok(RawHtml(cached));
+            return Ok(page_cache.respond(cached, "index"));
         }
     }
 
-    let template_list = Box::new(vec![
-        ("default", "default"),
-        ("navbar", "navbar"),
-        ("overlay", "overlay"),
-        ("layout", "layout"),
-        ("article", "blog"),
-    ]);
+    let components = [("parent", "layout"), ("article", "blog")];
     let data_list = [
         ("parent".to_string(), to_json("layout")),
-        ("site_name".to_string(), to_json("ISAALULA")),
         ("page_title".to_string(), to_json("Isa Al-Ula")),
         ("layout_min".to_string(), to_json(false)),
-        ("menus".to_string(), to_json(menus.get())),
-        ("default_theme".to_string(), to_json("mocha")),
-        ("secondary_theme".to_string(), to_json("latte")),
         ("article".to_string(), to_json(r#"<h1>INDEX</h1>"#)),
+        ("tag_cloud".to_string(), tag_cloud(articles).await),
     ];
     let data = make_data(&data_list);
     let html = render_page(
         "default",
-        template_pool,
-        &template_list,
+        &theme,
+        &components,
+        &site,
         data,
         page_cache,
         page,
+        Dependencies::default(),
     )
     .await;
     match html {
-        Ok(html) => Ok(RawHtml(html)),
+        Ok(html) => Ok(page_cache.respond(html, "index")),
         Err(e) => Err(NotFound(RawHtml(
-            make_404(template_pool, &e.to_string(), menus)
+            make_404(&theme, &e.to_string(), &site)
                 .await
                 .to_string(),
         ))),
     }
 }
 
-#[get("/<page>")]
-pub async fn pages(
-    page: &str,
-    template_pool: &State<TemplatePool>,
+#[get("/blog?<page>")]
+pub async fn blog_index(
+    page: Option<usize>,
+    theme: ActiveTheme,
     page_cache: &State<PageCache>,
-    menus: &State<Menus>,
-) -> StdResult<RawHtml<Arc<str>>, NotFound<RawHtml<String>>> {
-    let page_title: [&str; 3] = ["blog", "projects", "about"];
-    match page {
-        p if page_title.contains(&p) => {
-            if !cfg!(debug_assertions) {
-                let from_cache = get_page(page_cache, Duration::hours(1), page)
-                    .await
-                    .map_err(|e| e.to_string());
-                let cached: Option<Arc<str>> = match from_cache {
-                    Ok(o) => o,
-                    Err(e) => {
-                        return Err(NotFound(RawHtml(
-                            make_404(template_pool, &e.to_string(), menus)
-                                .await
-                                .to_string(),
-                        )))
-                    }
-                };
-
-                if let Some(cached) = cached {
-                    return Ok(RawHtml(cached));
-                }
+    site: SiteSettings,
+    articles: &State<ArticleRepository>,
+    config: &State<Config>,
+) -> StdResult<Page, NotFound<RawHtml<String>>> {
+    let page = page.unwrap_or(1).max(1);
+    let cache_id = format!("blog?page={}", page);
+
+    if !cfg!(debug_assertions) {
+        let from_cache = get_page(page_cache, &theme, page_cache.ttl("blog_index"), &cache_id)
+            .await
+            .map_err(|e| e.to_string());
+        let cached: Option<CachedPage> = match from_cache {
+            Ok(o) => o,
+            Err(e) => {
+                return Err(NotFound(RawHtml(
+                    make_404(&theme, &e.to_string(), &site)
+                        .await
+                        .to_string(),
+                )))
             }
-            let template_list = Box::new(vec![
-                ("default", "default"),
-                ("navbar", "navbar"),
-                ("overlay", "overlay"),
-                ("layout", "layout"),
-                ("article", "blog"),
-            ]);
-            let mut data_list = vec![
-                ("parent".to_string(), to_json("layout")),
-                ("site_name".to_string(), to_json("ISAALULA")),
-                ("layout_min".to_string(), to_json(false)),
-                ("menus".to_string(), to_json(menus.get())),
-                ("default_theme".to_string(), to_json("mocha")),
-                ("secondary_theme".to_string(), to_json("latte")),
-            ];
-            data_list.push((
-                "article".to_string(),
-                to_json(format!("<h1>{}</h1>", p.title_case())),
-            ));
-            data_list.push(("page_title".to_string(), to_json(p.title_case())));
-            let data = make_data(&data_list);
-            let html = render_page(
-                "default",
-                template_pool,
-                &template_list,
-                data,
-                page_cache,
-                page,
-            )
-            .await;
-            match html {
-                Ok(html) => Ok(RawHtml(html)),
-                Err(e) => Err(NotFound(RawHtml(
-                    make_404(template_pool, &e.to_string(), menus)
+        };
+
+        if let Some(cached) = cached {
+            return Ok(page_cache.respond(cached, "blog_index"));
+        }
+    }
+
+    let published = match articles.published("blog").await {
+        Ok(a) => a,
+        Err(e) => {
+            return Err(NotFound(RawHtml(
+                make_404(&theme, &e.to_string(), &site)
+                    .await
+                    .to_string(),
+            )))
+        }
+    };
+    let page_size = config.blog_page_size.max(1);
+    let total_pages = published.len().div_ceil(page_size).max(1);
+    if page > total_pages {
+        return Err(NotFound(RawHtml(
+            make_404(&theme, "page not found", &site)
+                .await
+                .to_string(),
+        )));
+    }
+    let mut previews = Vec::new();
+    let mut dependencies = Dependencies::default().with(Dependency::Section("blog".to_string()));
+    for article in published.iter().skip((page - 1) * page_size).take(page_size) {
+        dependencies = dependencies.article(article);
+        match article.preview().await {
+            Ok(p) => previews.push(p),
+            Err(e) => {
+                return Err(NotFound(RawHtml(
+                    make_404(&theme, &e.to_string(), &site)
                         .await
                         .to_string(),
-                ))),
+                )))
             }
         }
-        _ => Err(NotFound(RawHtml(
-            make_404(template_pool, "page not found", menus)
+    }
+    let pagination = Pagination::new(page, total_pages, "/blog");
+
+    let components = [("parent", "layout"), ("article", "article_preview")];
+    let data_list = [
+        ("parent".to_string(), to_json("layout")),
+        ("page_title".to_string(), to_json("Blog")),
+        ("layout_min".to_string(), to_json(false)),
+        ("articles".to_string(), to_json(&previews)),
+        ("pagination".to_string(), to_json(&pagination)),
+        ("tag_cloud".to_string(), tag_cloud(articles).await),
+    ];
+    let data = make_data(&data_list);
+    let html = render_page(
+        "default",
+        &theme,
+        &components,
+        &site,
+        data,
+        page_cache,
+        &cache_id,
+        dependencies,
+    )
+    .await;
+    match html {
+        Ok(html) => Ok(page_cache.respond(html, "blog_index")),
+        Err(e) => Err(NotFound(RawHtml(
+            make_404(&theme, &e.to_string(), &site)
                 .await
                 .to_string(),
         ))),
     }
 }
 
-#[get("/<page>/<article>")]
+#[get("/blog/<article>")]
 pub async fn blog(
-    page: &str,
     article: &str,
-    template_pool: &State<TemplatePool>,
+    theme: ActiveTheme,
     page_cache: &State<PageCache>,
-    menus: &State<Menus>,
-) -> StdResult<RawHtml<Arc<str>>, NotFound<RawHtml<String>>> {
-    match page {
-        "blog" => {
-            let blog_content = match read_to_string(format!("articles/blog/{}.md", article)).await {
-                Ok(s) => s,
-                Err(e) => {
-                    return Err(NotFound(RawHtml(
-                        make_404(template_pool, &e.to_string(), menus)
-                            .await
-                            .to_string(),
-                    )))
-                }
-            };
-            let title = article.snake_to_title_case();
-
-            if !cfg!(debug_assertions) {
-                let from_cache = get_page(page_cache, Duration::hours(1), &title)
+    site: SiteSettings,
+    articles: &State<ArticleRepository>,
+) -> StdResult<Page, NotFound<RawHtml<String>>> {
+    let page = "blog";
+    let blog_article = match articles.by_slug(page, article).await {
+        Ok(Some(a)) if !a.draft => a,
+        Ok(_) => {
+            return Err(NotFound(RawHtml(
+                make_404(&theme, "article not found", &site)
                     .await
-                    .map_err(|e| e.to_string());
-                let cached: Option<Arc<str>> = match from_cache {
-                    Ok(o) => o,
-                    Err(e) => {
-                        return Err(NotFound(RawHtml(
-                            make_404(template_pool, &e.to_string(), menus)
-                                .await
-                                .to_string(),
-                        )))
-                    }
-                };
-
-                if let Some(cached) = cached {
-                    return Ok(RawHtml(cached));
-                }
-            }
+                    .to_string(),
+            )))
+        }
+        Err(e) => {
+            return Err(NotFound(RawHtml(
+                make_404(&theme, &e.to_string(), &site)
+                    .await
+                    .to_string(),
+            )))
+        }
+    };
+    let title = blog_article.title.clone();
+    let cache_id = format!("{}/{}", page, article);
 
-            let html = match Markdown::new(blog_content).to_html(MarkdownType::Gfm) {
-                Ok(h) => h.to_string(),
-                Err(e) => {
-                    return Err(NotFound(RawHtml(
-                        make_404(template_pool, &e.to_string(), menus)
-                            .await
-                            .to_string(),
-                    )))
-                }
-            };
-            let template_list = Box::new(vec![
-                ("default", "default"),
-                ("navbar", "navbar"),
-                ("overlay", "overlay"),
-                ("layout", "layout"),
-                ("article", "blog"),
-            ]);
-            let data_list = [
-                ("parent".to_string(), to_json("layout")),
-                ("site_name".to_string(), to_json("ISAALULA")),
-                ("page_title".to_string(), to_json(&title)),
-                ("layout_min".to_string(), to_json(false)),
-                ("menus".to_string(), to_json(menus.get())),
-                ("default_theme".to_string(), to_json("mocha")),
-                ("secondary_theme".to_string(), to_json("latte")),
-                ("article".to_string(), to_json(html)),
-            ];
-            let data = make_data(&data_list);
-            let html = render_page(
-                "default",
-                template_pool,
-                &template_list,
-                data,
-                page_cache,
-                &title,
-            )
-            .await;
-            match html {
-                Ok(html) => Ok(RawHtml(html)),
-                Err(e) => Err(NotFound(
-                    make_404(template_pool, &e.to_string(), menus).await.into(),
-                )),
+    if !cfg!(debug_assertions) {
+        let from_cache = get_page(page_cache, &theme, page_cache.ttl("blog"), &cache_id)
+            .await
+            .map_err(|e| e.to_string());
+        let cached: Option<CachedPage> = match from_cache {
+            Ok(o) => o,
+            Err(e) => {
+                return Err(NotFound(RawHtml(
+                    make_404(&theme, &e.to_string(), &site)
+                        .await
+                        .to_string(),
+                )))
             }
+        };
+
+        if let Some(cached) = cached {
+            return Ok(page_cache.respond(cached, "blog"));
         }
-        _ => Err(NotFound(RawHtml(String::from("404")))),
+    }
+
+    let rendered = match &blog_article.content {
+        Content::Markdown(markdown) => markdown.to_html(MarkdownType::Gfm),
+        Content::Html(html) => Ok(html.clone()),
+    };
+    let html = match rendered {
+        Ok(h) => h.to_string(),
+        Err(e) => {
+            return Err(NotFound(RawHtml(
+                make_404(&theme, &e.to_string(), &site)
+                    .await
+                    .to_string(),
+            )))
+        }
+    };
+    let components = [("parent", "layout"), ("article", "blog")];
+    let data_list = [
+        ("parent".to_string(), to_json("layout")),
+        ("page_title".to_string(), to_json(&title)),
+        ("layout_min".to_string(), to_json(false)),
+        ("article".to_string(), to_json(html)),
+        ("tag_cloud".to_string(), tag_cloud(articles).await),
+    ];
+    let data = make_data(&data_list);
+    let html = render_page(
+        "default",
+        &theme,
+        &components,
+        &site,
+        data,
+        page_cache,
+        &cache_id,
+        Dependencies::default().article(&blog_article),
+    )
+    .await;
+    match html {
+        Ok(html) => Ok(page_cache.respond(html, "blog")),
+        Err(e) => Err(NotFound(
+            make_404(&theme, &e.to_string(), &site).await.into(),
+        )),
     }
 }
 
@@ -348,9 +406,92 @@ pub async fn blog(
 pub async fn static_files(
     file: PathBuf,
     config: &State<Config>,
+    theme: ActiveTheme,
 ) -> StdResult<NamedFile, NotFound<RawHtml<String>>> {
-    let theme_dir = &config.theme_dir;
-    let theme = &config.theme;
-    let file = theme_dir.join(theme.as_ref()).join("static").join(file);
+    let file = config
+        .theme_dir
+        .join(theme.name.as_ref())
+        .join(&theme.manifest.static_path)
+        .join(file);
     NamedFile::open(file).await.map_err(|e|NotFound(RawHtml(e.to_string())))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use figment::providers::Serialized;
+    use rocket::http::Status;
+    use rocket::local::asynchronous::Client;
+    use std::path::Path;
+    use ulid::Ulid;
+
+    /// A public server on a fresh db with the default theme, the `articles`
+    /// written below its articles directory.
+    pub(super) async fn client(articles: &[(&str, &str)], config: Config) -> Client {
+        let dir = std::env::temp_dir().join(format!("myweb-public-{}", Ulid::new()));
+        for (path, source) in articles {
+            let path = dir.join("articles").join(path);
+            tokio::fs::create_dir_all(path.parent().unwrap()).await.unwrap();
+            tokio::fs::write(path, source).await.unwrap();
+        }
+        let config = Config {
+            theme_dir: Path::new(env!("CARGO_MANIFEST_DIR")).join("theme").into(),
+            db_dir: dir.join("db").into(),
+            articles_dir: dir.join("articles").into(),
+            ..config
+        };
+        let figment = Figment::from(rocket::Config::debug_default())
+            .merge(Serialized::defaults(&config));
+        let db = Db::new(config.db_dir.clone());
+        let articles = ArticleRepository::index(config.articles_dir.clone()).await.unwrap();
+        let page_cache = PageCache::new(&config.cache);
+        let themes = ThemeRegistry::open(&config, &db).await.unwrap();
+        let rocket = launch(&figment, &db, &articles, &page_cache, &themes, None).await.unwrap();
+        Client::tracked(rocket).await.unwrap()
+    }
+
+    pub(super) async fn get(client: &Client, uri: &str) -> (Status, String) {
+        let response = client.get(uri.to_string()).dispatch().await;
+        (response.status(), response.into_string().await.unwrap_or_default())
+    }
+
+    fn blog(count: usize) -> Vec<(String, String)> {
+        (1..=count)
+            .map(|i| {
+                let source = format!("---\ndate: 2024-07-{:02}\n---\n# Article {}\n\nBody", i, i);
+                (format!("blog/article_{}.md", i), source)
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn paginates_the_published_articles() {
+        let mut articles = blog(3);
+        articles.push(("blog/draft.md".to_string(), "---\ndraft: true\n---\n# Draft\n\nBody".to_string()));
+        let articles: Vec<(&str, &str)> =
+            articles.iter().map(|(p, s)| (p.as_str(), s.as_str())).collect();
+        let client = client(&articles, Config { blog_page_size: 2, ..Config::default() }).await;
+
+        let (status, first) = get(&client, "/blog").await;
+        assert_eq!(status, Status::Ok);
+        assert!(first.contains("Article 3") && first.contains("Article 2"));
+        assert!(!first.contains("Article 1") && !first.contains("Draft"));
+        assert!(first.contains("/blog?page=2"));
+        assert_eq!(get(&client, "/blog?page=1").await.1, first);
+        assert_eq!(get(&client, "/blog?page=0").await.1, first);
+
+        let (status, second) = get(&client, "/blog?page=2").await;
+        assert_eq!(status, Status::Ok);
+        assert!(second.contains("Article 1") && !second.contains("Article 2"));
+        assert!(!second.contains("/blog?page=3"));
+
+        assert_eq!(get(&client, "/blog?page=3").await.0, Status::NotFound);
+    }
+
+    #[tokio::test]
+    async fn shows_an_empty_first_page() {
+        let client = client(&[], Config::default()).await;
+        assert_eq!(get(&client, "/blog").await.0, Status::Ok);
+        assert_eq!(get(&client, "/blog?page=2").await.0, Status::NotFound);
+    }
+}
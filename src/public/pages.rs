@@ -0,0 +1,180 @@
+use super::pagination::Pagination;
+use super::{make_404, tag_cloud, StdResult};
+use crate::config::Config;
+use crate::page::{FindPage, PageDef, PageSource, PageStore};
+use crate::post::article::Content;
+use crate::post::{ArticleRepository, Markdown, MarkdownType, PreviewArticle};
+use crate::render::{get_page, make_data, render_page, CachedPage, Dependencies, Dependency, Page, PageCache};
+use crate::settings::SiteSettings;
+use crate::theme::ActiveTheme;
+use crate::Error;
+use std::time::Duration;
+use handlebars::to_json;
+use rocket::response::{content::RawHtml, status::NotFound};
+use rocket::{get, State};
+use serde_json::Value;
+use std::path::PathBuf;
+
+/// Template variables of the page's source and the resources they come from,
+/// `None` when there is nothing to show, like a missing article or a page past
+/// the last one.
+async fn source_data(
+    def: &PageDef,
+    page: usize,
+    articles: &ArticleRepository,
+    config: &Config,
+) -> Result<Option<(Vec<(String, Value)>, Dependencies)>, Error> {
+    let dependencies = Dependencies::default().with(Dependency::Page(def.slug.clone()));
+    let data = match &def.source {
+        PageSource::Html { content } => {
+            (vec![("article".to_string(), to_json(content))], dependencies)
+        }
+        PageSource::Markdown { content } => {
+            let html = Markdown::new(content.clone()).to_html(MarkdownType::Gfm)?;
+            (vec![("article".to_string(), to_json(html.to_string()))], dependencies)
+        }
+        PageSource::Article { section, slug } => {
+            let article = match articles.by_slug(section, slug).await? {
+                Some(a) if !a.draft => a,
+                _ => return Ok(None),
+            };
+            let html = match &article.content {
+                Content::Markdown(markdown) => markdown.to_html(MarkdownType::Gfm)?,
+                Content::Html(html) => html.clone(),
+            };
+            let data = vec![("article".to_string(), to_json(html.to_string()))];
+            (data, dependencies.article(&article))
+        }
+        PageSource::Articles { section } => {
+            let published = articles.published(section).await?;
+            let page_size = config.blog_page_size.max(1);
+            let total_pages = published.len().div_ceil(page_size).max(1);
+            if page > total_pages {
+                return Ok(None);
+            }
+            let mut previews = Vec::new();
+            let mut dependencies = dependencies.with(Dependency::Section(section.clone()));
+            for article in published.iter().skip((page - 1) * page_size).take(page_size) {
+                dependencies = dependencies.article(article);
+                previews.push(article.preview().await?);
+            }
+            let pagination = Pagination::new(page, total_pages, &format!("/{}", def.slug));
+            let data = vec![
+                ("articles".to_string(), to_json(&previews)),
+                ("pagination".to_string(), to_json(&pagination)),
+            ];
+            (data, dependencies)
+        }
+    };
+    Ok(Some(data))
+}
+
+/// Serves the configured and stored [`PageDef`]s.
+#[allow(clippy::too_many_arguments)]
+#[get("/<path..>?<page>", rank = 20)]
+pub async fn page(
+    path: PathBuf,
+    page: Option<usize>,
+    theme: ActiveTheme,
+    page_cache: &State<PageCache>,
+    site: SiteSettings,
+    articles: &State<ArticleRepository>,
+    pages: &State<PageStore>,
+    config: &State<Config>,
+) -> StdResult<Page, NotFound<RawHtml<String>>> {
+    let slug = path.to_string_lossy().to_string();
+    let def = match pages.page(&slug, articles, &config.pages).await {
+        Ok(Some(def)) => def,
+        Ok(None) => {
+            return Err(NotFound(RawHtml(
+                make_404(&theme, "page not found", &site)
+                    .await
+                    .to_string(),
+            )))
+        }
+        Err(e) => {
+            return Err(NotFound(RawHtml(
+                make_404(&theme, &e.to_string(), &site)
+                    .await
+                    .to_string(),
+            )))
+        }
+    };
+    let page = page.unwrap_or(1).max(1);
+    let cache_id = match def.source {
+        PageSource::Articles { .. } => format!("{}?page={}", slug, page),
+        _ => slug.clone(),
+    };
+
+    if !cfg!(debug_assertions) {
+        let ttl = def.cache_ttl.map_or(page_cache.ttl("pages"), Duration::from_secs);
+        let from_cache = get_page(page_cache, &theme, ttl, &cache_id)
+            .await
+            .map_err(|e| e.to_string());
+        let cached: Option<CachedPage> = match from_cache {
+            Ok(o) => o,
+            Err(e) => {
+                return Err(NotFound(RawHtml(
+                    make_404(&theme, &e.to_string(), &site)
+                        .await
+                        .to_string(),
+                )))
+            }
+        };
+
+        if let Some(cached) = cached {
+            return Ok(page_cache.respond(cached, "pages"));
+        }
+    }
+
+    let (source, dependencies) = match source_data(&def, page, articles, config).await {
+        Ok(Some(data)) => data,
+        Ok(None) => {
+            return Err(NotFound(RawHtml(
+                make_404(&theme, "page not found", &site)
+                    .await
+                    .to_string(),
+            )))
+        }
+        Err(e) => {
+            return Err(NotFound(RawHtml(
+                make_404(&theme, &e.to_string(), &site)
+                    .await
+                    .to_string(),
+            )))
+        }
+    };
+    let components = def.overrides();
+    let parent = components
+        .iter()
+        .find(|(partial, _)| *partial == "parent")
+        .map_or("layout", |(_, component)| component);
+    let mut data_list: Vec<(String, Value)> = def.data.clone().into_iter().collect();
+    data_list.extend([
+        ("parent".to_string(), to_json(parent)),
+        ("page_title".to_string(), to_json(def.title())),
+        ("layout_min".to_string(), to_json(def.layout_min)),
+        ("tag_cloud".to_string(), tag_cloud(articles).await),
+    ]);
+    data_list.extend(source);
+    let data = make_data(&data_list);
+    let html = render_page(
+        &def.template,
+        &theme,
+        &components,
+        &site,
+        data,
+        page_cache,
+        &cache_id,
+        dependencies,
+    )
+    .await;
+    match html {
+        Ok(html) => Ok(page_cache.respond(html, "pages")),
+        Err(e) => Err(NotFound(RawHtml(
+            make_404(&theme, &e.to_string(), &site)
+                .await
+                .to_string(),
+        ))),
+    }
+}
@@ -0,0 +1,24 @@
+use rocket::serde::Serialize;
+
+#[derive(Serialize, Debug)]
+pub struct Pagination {
+    pub page: usize,
+    pub total_pages: usize,
+    pub previous: Option<String>,
+    pub next: Option<String>,
+}
+
+impl Pagination {
+    pub fn new(page: usize, total_pages: usize, base_url: &str) -> Self {
+        let link = |page: usize| match page {
+            1 => base_url.to_string(),
+            p => format!("{}?page={}", base_url, p),
+        };
+        Pagination {
+            page,
+            total_pages,
+            previous: (page > 1).then(|| link(page - 1)),
+            next: (page < total_pages).then(|| link(page + 1)),
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use super::{make_404, tag_cloud, StdResult};
+use crate::post::article::Content;
+use crate::post::project::{sort_projects, Project, PROJECTS_SECTION};
+use crate::post::{ArticleRepository, MarkdownType};
+use crate::render::{get_page, make_data, render_page, CachedPage, Dependencies, Dependency, Page, PageCache};
+use crate::settings::SiteSettings;
+use crate::theme::ActiveTheme;
+use handlebars::to_json;
+use rocket::response::{content::RawHtml, status::NotFound};
+use rocket::{get, State};
+
+#[get("/projects")]
+pub async fn projects(
+    theme: ActiveTheme,
+    page_cache: &State<PageCache>,
+    site: SiteSettings,
+    articles: &State<ArticleRepository>,
+) -> StdResult<Page, NotFound<RawHtml<String>>> {
+    let cache_id = PROJECTS_SECTION;
+
+    if !cfg!(debug_assertions) {
+        let from_cache = get_page(page_cache, &theme, page_cache.ttl("projects"), cache_id)
+            .await
+            .map_err(|e| e.to_string());
+        let cached: Option<CachedPage> = match from_cache {
+            Ok(o) => o,
+            Err(e) => {
+                return Err(NotFound(RawHtml(
+                    make_404(&theme, &e.to_string(), &site)
+                        .await
+                        .to_string(),
+                )))
+            }
+        };
+
+        if let Some(cached) = cached {
+            return Ok(page_cache.respond(cached, "projects"));
+        }
+    }
+
+    let published = match articles.published(PROJECTS_SECTION).await {
+        Ok(a) => a,
+        Err(e) => {
+            return Err(NotFound(RawHtml(
+                make_404(&theme, &e.to_string(), &site)
+                    .await
+                    .to_string(),
+            )))
+        }
+    };
+    let mut projects = Vec::new();
+    for article in published.iter() {
+        match Project::from_article(article).await {
+            Ok(p) => projects.push(p),
+            Err(e) => {
+                return Err(NotFound(RawHtml(
+                    make_404(&theme, &e.to_string(), &site)
+                        .await
+                        .to_string(),
+                )))
+            }
+        }
+    }
+    sort_projects(&mut projects);
+
+    let components = [("parent", "layout"), ("article", "projects")];
+    let data_list = [
+        ("parent".to_string(), to_json("layout")),
+        ("page_title".to_string(), to_json("Projects")),
+        ("layout_min".to_string(), to_json(false)),
+        ("projects".to_string(), to_json(&projects)),
+        ("tag_cloud".to_string(), tag_cloud(articles).await),
+    ];
+    let data = make_data(&data_list);
+    let html = render_page(
+        "default",
+        &theme,
+        &components,
+        &site,
+        data,
+        page_cache,
+        cache_id,
+        Dependencies::default()
+            .with(Dependency::Section(PROJECTS_SECTION.to_string()))
+            .articles(&published),
+    )
+    .await;
+    match html {
+        Ok(html) => Ok(page_cache.respond(html, "projects")),
+        Err(e) => Err(NotFound(RawHtml(
+            make_404(&theme, &e.to_string(), &site)
+                .await
+                .to_string(),
+        ))),
+    }
+}
+
+#[get("/projects/<slug>")]
+pub async fn project(
+    slug: &str,
+    theme: ActiveTheme,
+    page_cache: &State<PageCache>,
+    site: SiteSettings,
+    articles: &State<ArticleRepository>,
+) -> StdResult<Page, NotFound<RawHtml<String>>> {
+    let article = match articles.by_slug(PROJECTS_SECTION, slug).await {
+        Ok(Some(a)) if !a.draft => a,
+        Ok(_) => {
+            return Err(NotFound(RawHtml(
+                make_404(&theme, "project not found", &site)
+                    .await
+                    .to_string(),
+            )))
+        }
+        Err(e) => {
+            return Err(NotFound(RawHtml(
+                make_404(&theme, &e.to_string(), &site)
+                    .await
+                    .to_string(),
+            )))
+        }
+    };
+    let cache_id = format!("{}/{}", PROJECTS_SECTION, slug);
+
+    if !cfg!(debug_assertions) {
+        let from_cache = get_page(page_cache, &theme, page_cache.ttl("project"), &cache_id)
+            .await
+            .map_err(|e| e.to_string());
+        let cached: Option<CachedPage> = match from_cache {
+            Ok(o) => o,
+            Err(e) => {
+                return Err(NotFound(RawHtml(
+                    make_404(&theme, &e.to_string(), &site)
+                        .await
+                        .to_string(),
+                )))
+            }
+        };
+
+        if let Some(cached) = cached {
+            return Ok(page_cache.respond(cached, "project"));
+        }
+    }
+
+    let project = match Project::from_article(&article).await {
+        Ok(p) => p,
+        Err(e) => {
+            return Err(NotFound(RawHtml(
+                make_404(&theme, &e.to_string(), &site)
+                    .await
+                    .to_string(),
+            )))
+        }
+    };
+    let rendered = match &article.content {
+        Content::Markdown(markdown) => markdown.to_html(MarkdownType::Gfm),
+        Content::Html(html) => Ok(html.clone()),
+    };
+    let html = match rendered {
+        Ok(h) => h.to_string(),
+        Err(e) => {
+            return Err(NotFound(RawHtml(
+                make_404(&theme, &e.to_string(), &site)
+                    .await
+                    .to_string(),
+            )))
+        }
+    };
+
+    let components = [("parent", "layout"), ("article", "project")];
+    let data_list = [
+        ("parent".to_string(), to_json("layout")),
+        ("page_title".to_string(), to_json(&project.name)),
+        ("layout_min".to_string(), to_json(false)),
+        ("project".to_string(), to_json(&project)),
+        ("article".to_string(), to_json(html)),
+        ("tag_cloud".to_string(), tag_cloud(articles).await),
+    ];
+    let data = make_data(&data_list);
+    let html = render_page(
+        "default",
+        &theme,
+        &components,
+        &site,
+        data,
+        page_cache,
+        &cache_id,
+        Dependencies::default().article(&article),
+    )
+    .await;
+    match html {
+        Ok(html) => Ok(page_cache.respond(html, "project")),
+        Err(e) => Err(NotFound(RawHtml(
+            make_404(&theme, &e.to_string(), &site)
+                .await
+                .to_string(),
+        ))),
+    }
+}
@@ -0,0 +1,174 @@
+use super::pagination::Pagination;
+use super::{make_404, tag_cloud, StdResult};
+use crate::config::Config;
+use crate::post::taxonomy::{term_slug, Taxonomy};
+use crate::post::{ArticleRepository, PreviewArticle};
+use crate::render::{get_page, make_data, render_page, CachedPage, Dependencies, Dependency, Page, PageCache};
+use crate::settings::SiteSettings;
+use crate::theme::ActiveTheme;
+use handlebars::to_json;
+use rocket::response::{content::RawHtml, status::NotFound};
+use rocket::{get, State};
+use serde_json::json;
+
+#[allow(clippy::too_many_arguments)]
+async fn term_page(
+    taxonomy: Taxonomy,
+    term: &str,
+    page: Option<usize>,
+    theme: &ActiveTheme,
+    page_cache: &State<PageCache>,
+    site: &SiteSettings,
+    articles: &State<ArticleRepository>,
+    config: &State<Config>,
+) -> StdResult<Page, NotFound<RawHtml<String>>> {
+    let page = page.unwrap_or(1).max(1);
+    let base_url = format!("/{}/{}", taxonomy.path(), term_slug(term));
+    let cache_id = format!("{}?page={}", base_url, page);
+
+    if !cfg!(debug_assertions) {
+        let from_cache = get_page(page_cache, theme, page_cache.ttl(taxonomy.path()), &cache_id)
+            .await
+            .map_err(|e| e.to_string());
+        let cached: Option<CachedPage> = match from_cache {
+            Ok(o) => o,
+            Err(e) => {
+                return Err(NotFound(RawHtml(
+                    make_404(theme, &e.to_string(), site)
+                        .await
+                        .to_string(),
+                )))
+            }
+        };
+
+        if let Some(cached) = cached {
+            return Ok(page_cache.respond(cached, taxonomy.path()));
+        }
+    }
+
+    let (name, tagged) = match articles.by_term(taxonomy, term).await {
+        Ok(Some(t)) => t,
+        Ok(None) => {
+            return Err(NotFound(RawHtml(
+                make_404(theme, "page not found", site)
+                    .await
+                    .to_string(),
+            )))
+        }
+        Err(e) => {
+            return Err(NotFound(RawHtml(
+                make_404(theme, &e.to_string(), site)
+                    .await
+                    .to_string(),
+            )))
+        }
+    };
+    let page_size = config.blog_page_size.max(1);
+    let total_pages = tagged.len().div_ceil(page_size).max(1);
+    if page > total_pages {
+        return Err(NotFound(RawHtml(
+            make_404(theme, "page not found", site)
+                .await
+                .to_string(),
+        )));
+    }
+    let mut previews = Vec::new();
+    let mut dependencies =
+        Dependencies::default().with(Dependency::Term(taxonomy, term_slug(term)));
+    for article in tagged.iter().skip((page - 1) * page_size).take(page_size) {
+        dependencies = dependencies.article(article);
+        match article.preview().await {
+            Ok(p) => previews.push(p),
+            Err(e) => {
+                return Err(NotFound(RawHtml(
+                    make_404(theme, &e.to_string(), site)
+                        .await
+                        .to_string(),
+                )))
+            }
+        }
+    }
+    let pagination = Pagination::new(page, total_pages, &base_url);
+
+    let components = [("parent", "layout"), ("article", "article_preview")];
+    let data_list = [
+        ("parent".to_string(), to_json("layout")),
+        ("page_title".to_string(), to_json(&name)),
+        ("layout_min".to_string(), to_json(false)),
+        (
+            "term".to_string(),
+            json!({ "name": name, "taxonomy": taxonomy.path(), "count": tagged.len() }),
+        ),
+        ("articles".to_string(), to_json(&previews)),
+        ("pagination".to_string(), to_json(&pagination)),
+        ("tag_cloud".to_string(), tag_cloud(articles).await),
+    ];
+    let data = make_data(&data_list);
+    let html = render_page(
+        "default",
+        theme,
+        &components,
+        site,
+        data,
+        page_cache,
+        &cache_id,
+        dependencies,
+    )
+    .await;
+    match html {
+        Ok(html) => Ok(page_cache.respond(html, taxonomy.path())),
+        Err(e) => Err(NotFound(RawHtml(
+            make_404(theme, &e.to_string(), site)
+                .await
+                .to_string(),
+        ))),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+#[get("/tags/<tag>?<page>")]
+pub async fn tag(
+    tag: &str,
+    page: Option<usize>,
+    theme: ActiveTheme,
+    page_cache: &State<PageCache>,
+    site: SiteSettings,
+    articles: &State<ArticleRepository>,
+    config: &State<Config>,
+) -> StdResult<Page, NotFound<RawHtml<String>>> {
+    term_page(
+        Taxonomy::Tags,
+        tag,
+        page,
+        &theme,
+        page_cache,
+        &site,
+        articles,
+        config,
+    )
+    .await
+}
+
+#[allow(clippy::too_many_arguments)]
+#[get("/categories/<name>?<page>")]
+pub async fn category(
+    name: &str,
+    page: Option<usize>,
+    theme: ActiveTheme,
+    page_cache: &State<PageCache>,
+    site: SiteSettings,
+    articles: &State<ArticleRepository>,
+    config: &State<Config>,
+) -> StdResult<Page, NotFound<RawHtml<String>>> {
+    term_page(
+        Taxonomy::Categories,
+        name,
+        page,
+        &theme,
+        page_cache,
+        &site,
+        articles,
+        config,
+    )
+    .await
+}
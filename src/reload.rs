@@ -0,0 +1,134 @@
+//! Hot reload for development, enabled by [`Config::hot_reload`].
+//!
+//! A file watcher reads the active theme again when one of its files changes
+//! and re-indexes changed articles, drops the cached pages depending on them
+//! and tells the open pages to reload over the `/live-reload` event stream.
+
+use std::fs::canonicalize;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::sleep;
+use crate::config::Config;
+use crate::post::ArticleRepository;
+use crate::render::{purge_article, PageCache};
+use crate::theme::ThemeRegistry;
+use crate::Error;
+
+/// Editors save a file in several steps, changes this close are taken as one.
+const SETTLE: Duration = Duration::from_millis(100);
+
+/// Announces reloads to the subscribed pages.
+#[derive(Clone)]
+pub struct LiveReload(broadcast::Sender<()>);
+
+impl LiveReload {
+    pub fn subscribe(&self) -> broadcast::Receiver<()> {
+        self.0.subscribe()
+    }
+}
+
+struct Watched {
+    theme_dir: PathBuf,
+    articles_dir: Option<PathBuf>,
+    themes: ThemeRegistry,
+    articles: ArticleRepository,
+    page_cache: PageCache,
+}
+
+/// Starts watching `theme_dir` and `articles_dir` until the process ends.
+pub fn watch(
+    config: &Config,
+    themes: &ThemeRegistry,
+    articles: &ArticleRepository,
+    page_cache: &PageCache,
+) -> Result<LiveReload, Error> {
+    let watched = Watched {
+        theme_dir: canonicalize(&config.theme_dir)?,
+        articles_dir: canonicalize(&config.articles_dir).ok(),
+        themes: themes.clone(),
+        articles: articles.clone(),
+        page_cache: page_cache.clone(),
+    };
+    let (sender, mut changes) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+        if let Ok(event) = event {
+            if !matches!(event.kind, EventKind::Access(_)) {
+                let _ = sender.send(event.paths);
+            }
+        }
+    })
+    .map_err(|e| format!("Failed to start the file watcher: {}", e))?;
+    for dir in [Some(&watched.theme_dir), watched.articles_dir.as_ref()].into_iter().flatten() {
+        watcher
+            .watch(dir, RecursiveMode::Recursive)
+            .map_err(|e| format!("Failed to watch {}: {}", dir.display(), e))?;
+    }
+
+    let live_reload = LiveReload(broadcast::channel(16).0);
+    let reloads = live_reload.0.clone();
+    tokio::spawn(async move {
+        let _watcher = watcher;
+        while let Some(mut paths) = changes.recv().await {
+            sleep(SETTLE).await;
+            while let Ok(more) = changes.try_recv() {
+                paths.extend(more);
+            }
+            paths.sort();
+            paths.dedup();
+            if watched.apply(&paths).await {
+                let _ = reloads.send(());
+            }
+        }
+    });
+    Ok(live_reload)
+}
+
+impl Watched {
+    /// Reloads what `paths` belong to, whether anything did.
+    async fn apply(&self, paths: &[PathBuf]) -> bool {
+        let mut changed = false;
+        let theme = self.theme_dir.join(self.themes.active().await.name.as_ref());
+        if paths.iter().any(|p| p.starts_with(&theme)) {
+            match self.themes.reload().await {
+                // The reloaded theme starts a new generation, the page cache
+                // drops the pages of the old templates with it.
+                Ok(Some(_)) => changed = true,
+                // An admin switched themes meanwhile, nothing to reload.
+                Ok(None) => {}
+                Err(e) => rocket::warn!("Failed to reload theme {}: {}", theme.display(), e),
+            }
+        }
+        let Some(articles_dir) = &self.articles_dir else {
+            return changed;
+        };
+        for path in paths {
+            let Some(section) = article_section(articles_dir, path) else {
+                continue;
+            };
+            match self.reload_article(section, path).await {
+                Ok(()) => changed = true,
+                Err(e) => rocket::warn!("Failed to reload {}: {}", path.display(), e),
+            }
+        }
+        changed
+    }
+
+    async fn reload_article(&self, section: &str, path: &Path) -> Result<(), Error> {
+        let (previous, current) = self.articles.reload_file(section, path).await?;
+        purge_article(&self.page_cache, previous.as_ref(), current.as_ref()).await
+    }
+}
+
+/// Section of `path` if it is an article file, `<articles_dir>/<section>/<slug>.<md|html>`.
+fn article_section<'a>(articles_dir: &Path, path: &'a Path) -> Option<&'a str> {
+    if !path.extension().is_some_and(|e| e == "md" || e == "html") {
+        return None;
+    }
+    let mut components = path.strip_prefix(articles_dir).ok()?.components();
+    match (components.next(), components.next(), components.next()) {
+        (Some(Component::Normal(section)), Some(Component::Normal(_)), None) => section.to_str(),
+        _ => None,
+    }
+}
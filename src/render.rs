@@ -1,18 +1,22 @@
 use std::sync::Arc;
 use crate::Error;
-use chrono::Duration;
-use handlebars::Handlebars;
+use std::time::Duration;
+use handlebars::{html_escape, no_escape, Handlebars};
 use rocket::State;
 use serde_json::{Map, Value};
-use tokio::time::Instant;
 
 use crate::{
-    post::Html,
-    template::{GetTemplate, TemplatePool},
+    post::{article::Article, Html},
+    settings::SiteSettings,
+    template::GetTemplate,
+    theme::{ActiveTheme, ComponentTree, Variable},
 };
-use crate::db::mem::Data;
+use crate::db::{Data, Store};
 
-pub type PageCache = Data<String, (Arc<str>, Instant)>;
+pub use crate::cache::{CachedPage, Dependencies, Dependency, Page, PageCache};
+
+/// Compiled templates by the partials registered in them, see [`registry`].
+pub type RegistryCache = Data<Vec<(String, String)>, Arc<Handlebars<'static>>>;
 
 pub fn make_data(data_list: &[(String, Value)]) -> Map<String, Value> {
     let data_list = data_list.to_owned();
@@ -23,54 +27,231 @@ pub fn make_data(data_list: &[(String, Value)]) -> Map<String, Value> {
     data
 }
 
+/// Renders `page_template` with `data` on top of the site settings' variables.
+///
+/// `overrides` picks the components of the page's partials, see
+/// [`crate::theme::Theme::resolve`]. The data is checked and escaped by
+/// [`prepare_data`] first.
 pub async fn render(
     page_template: &str,
-    template_pool: &State<TemplatePool>,
-    template_list: &[(&str, &str)],
+    theme: &ActiveTheme,
+    overrides: &[(&str, &str)],
+    site: &SiteSettings,
     data: Map<String, Value>,
 ) -> Result<Html, Error> {
-    let mut handlebars = Handlebars::new();
-    for t in template_list.iter() {
-        let template = template_pool.get_template(t.1).await?; 
-        handlebars.register_template_string(t.0, template).map_err(|e| format!("Template Error: {}", e))?;
-    }
+    let tree = theme.manifest.resolve(page_template, overrides)?;
+    render_tree(page_template, theme, &tree, site, data).await
+}
+
+async fn render_tree(
+    page_template: &str,
+    theme: &ActiveTheme,
+    tree: &ComponentTree<'_>,
+    site: &SiteSettings,
+    data: Map<String, Value>,
+) -> Result<Html, Error> {
+    let mut site_data = site.template_data();
+    site_data.extend(data);
+    let data = prepare_data(tree, site_data)?;
+    let handlebars = registry(theme, &tree.partials).await?;
     let hb = Html::new(handlebars.render(page_template, &data).map_err(|e| format!("Render Error: {}", e))?).minify()?;
     Ok(hb)
 }
 
+/// Handlebars with the `partials` of a [`ComponentTree`] registered.
+///
+/// Templates are compiled once per combination of partials and kept with the
+/// theme, a reloaded or switched theme starts over with fresh templates.
+pub async fn registry(
+    theme: &ActiveTheme,
+    partials: &[(String, String)],
+) -> Result<Arc<Handlebars<'static>>, Error> {
+    let key = partials.to_vec();
+    if let Some(handlebars) = theme.registries.get(&key).await? {
+        return Ok(handlebars);
+    }
+    let mut handlebars = Handlebars::new();
+    handlebars.register_escape_fn(no_escape);
+    for t in partials {
+        let template = theme.templates.get_template(&t.1).await?;
+        handlebars.register_template_string(&t.0, template).map_err(|e| format!("Template Error: {}", e))?;
+    }
+    let handlebars = Arc::new(handlebars);
+    theme.registries.insert(key, handlebars.clone()).await?;
+    Ok(handlebars)
+}
+
+/// Checks `data` against the variables the templates declare and escapes it.
+///
+/// Missing variables take the tree's defaults, then every declared variable
+/// must be present with its declared type, `null` being a fine `Value`.
+/// Handlebars does not escape, so `String` values and the strings inside
+/// `Value` and undeclared values are HTML escaped here while `Raw` values are
+/// passed through, whether the template uses `{{` or `{{{`.
+pub fn prepare_data(tree: &ComponentTree, mut data: Map<String, Value>) -> Result<Map<String, Value>, Error> {
+    for (name, value) in &tree.defaults {
+        if !data.contains_key(name) {
+            data.insert(name.clone(), value.clone());
+        }
+    }
+    let declared = &tree.variables;
+    let mut problems = Vec::new();
+    for (variable, name) in declared {
+        match data.get(*name) {
+            None => problems.push(format!("{} is missing", name)),
+            Some(value) if !variable.accepts(value) => {
+                problems.push(format!("{} should be {:?}, got {}", name, variable, value))
+            }
+            Some(_) => {}
+        }
+    }
+    if !problems.is_empty() {
+        return Err(format!("Invalid template data: {}", problems.join(", ")).into());
+    }
+    Ok(data
+        .into_iter()
+        .map(|(name, value)| {
+            match declared.iter().any(|(v, n)| *n == name && *v == Variable::Raw) {
+                true => (name, value),
+                false => (name, escape(value)),
+            }
+        })
+        .collect())
+}
+
+fn escape(value: Value) -> Value {
+    match value {
+        Value::String(s) => Value::String(html_escape(&s)),
+        Value::Array(a) => Value::Array(a.into_iter().map(escape).collect()),
+        Value::Object(o) => Value::Object(o.into_iter().map(|(k, v)| (k, escape(v))).collect()),
+        value => value,
+    }
+}
+
+/// Renders and caches a page with the resources it depends on, the templates
+/// it is made of added to the route's `dependencies`.
+#[allow(clippy::too_many_arguments)]
 pub async fn render_page(
     page_template: &str,
-    template_pool: &State<TemplatePool>,
-    template_list: &[(&str, &str)],
+    theme: &ActiveTheme,
+    overrides: &[(&str, &str)],
+    site: &SiteSettings,
     data: Map<String, Value>,
     page_cache: &State<PageCache>,
     cache_id: &str,
-) -> Result<Arc<str>, Error> {
-        let generated_page: Arc<str> = render(page_template, template_pool, template_list, data)
+    dependencies: Dependencies,
+) -> Result<CachedPage, Error> {
+        let tree = theme.manifest.resolve(page_template, overrides)?;
+        let generated_page: Arc<str> = render_tree(page_template, theme, &tree, site, data)
             .await?
             .minify()?.to_string().into();
 
-        // Store the generated page in the cache
-        {
-            page_cache.delete(&cache_id.to_string()).await?;
-            page_cache.insert(
-                cache_id.to_string(),
-                (generated_page.clone(), Instant::now()),
-            ).await?;
-        }
-        Ok(generated_page)
+        let templates = tree.partials.iter().map(|(_, template)| Dependency::Template(template.clone()));
+        let dependencies = dependencies.extend(templates);
+        let page = CachedPage::new(generated_page, dependencies.last_modified);
+        page_cache
+            .insert(cache_id.to_string(), page.clone(), &dependencies, theme.generation)
+            .await;
+        Ok(page)
+}
+
+/// The cached page if it is younger than `cache_duration` and was rendered
+/// with `theme`, `None` when it has to be rendered.
+pub async fn get_page(
+    page_cache: &State<PageCache>,
+    theme: &ActiveTheme,
+    cache_duration: Duration,
+    cache_id: &str,
+) -> Result<Option<CachedPage>, Error> {
+    Ok(page_cache.get(cache_id, cache_duration, theme.generation).await)
+}
+
+/// Purges the public pages that show or list an article, `previous` and
+/// `current` being its versions before and after the change.
+pub async fn purge_article(
+    page_cache: &PageCache,
+    previous: Option<&Article>,
+    current: Option<&Article>,
+) -> Result<(), Error> {
+    page_cache.invalidate(&Dependencies::changed(previous, current)).await;
+    Ok(())
 }
 
-pub async fn get_page(page_cache: &State<PageCache>, cache_duration: Duration, cache_id: &str) -> Result<Option<Arc<str>>, Error> {
-    let cache = page_cache.get(&cache_id.to_string()).await?;
-    if let Some((page, timestamp)) = cache {
-        if timestamp.elapsed() < cache_duration.to_std().map_err(|e| format!("Out of range: {}", e))? {
-            Ok(Some(page.clone()))
-        } else {
-            page_cache.delete(&cache_id.to_string()).await?;
-            Ok(Some(page.clone()))
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn tree<'a>(variables: Vec<(Variable, &'a str)>, defaults: Value) -> ComponentTree<'a> {
+        ComponentTree {
+            partials: Vec::new(),
+            variables,
+            defaults: defaults.as_object().cloned().unwrap_or_default(),
         }
-    } else { 
-        Ok(None)
     }
-}
\ No newline at end of file
+
+    fn data(value: Value) -> Map<String, Value> {
+        value.as_object().cloned().unwrap()
+    }
+
+    #[test]
+    fn escapes_all_but_raw_values() {
+        let tree = tree(
+            vec![
+                (Variable::String, "title"),
+                (Variable::Raw, "article"),
+                (Variable::Value, "tags"),
+            ],
+            Value::Null,
+        );
+        let data = prepare_data(
+            &tree,
+            data(json!({
+                "title": "<b>Tom & Jerry</b>",
+                "article": "<p>Body</p>",
+                "tags": [{ "name": "<i>" }],
+                "undeclared": "<script>",
+            })),
+        )
+        .unwrap();
+        assert_eq!(data["title"], "&lt;b&gt;Tom &amp; Jerry&lt;/b&gt;");
+        assert_eq!(data["article"], "<p>Body</p>");
+        assert_eq!(data["tags"], json!([{ "name": "&lt;i&gt;" }]));
+        assert_eq!(data["undeclared"], "&lt;script&gt;");
+    }
+
+    #[test]
+    fn fills_missing_variables_with_the_defaults() {
+        let tree = tree(
+            vec![(Variable::Bool, "layout_min"), (Variable::String, "title")],
+            json!({ "layout_min": false, "title": "Default" }),
+        );
+        let data = prepare_data(&tree, data(json!({ "title": "Given" }))).unwrap();
+        assert_eq!(data["layout_min"], false);
+        assert_eq!(data["title"], "Given");
+    }
+
+    #[test]
+    fn reports_every_missing_or_mistyped_variable() {
+        let tree = tree(
+            vec![
+                (Variable::Bool, "layout_min"),
+                (Variable::String, "title"),
+                (Variable::Raw, "article"),
+                (Variable::Value, "extra"),
+            ],
+            Value::Null,
+        );
+        let error = prepare_data(
+            &tree,
+            data(json!({ "layout_min": "yes", "article": 1, "extra": null })),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("layout_min should be Bool"), "{}", error);
+        assert!(error.contains("title is missing"), "{}", error);
+        assert!(error.contains("article should be Raw"), "{}", error);
+        // `null` is a fine `Value`.
+        assert!(!error.contains("extra"), "{}", error);
+    }
+}
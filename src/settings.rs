@@ -0,0 +1,180 @@
+use async_trait::async_trait;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome};
+use rocket::Request;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::config::Config;
+use crate::db::{FileData, Store};
+use crate::page::page_menus;
+use crate::post::ArticleRepository;
+use crate::public::Menus;
+use crate::Error;
+
+pub type SettingsStore = FileData<String, SiteSettings>;
+
+const SITE_KEY: &str = "site";
+
+/// Site wide values every page template gets.
+///
+/// The `[default.site]` table of the config provides them until they are
+/// saved through the admin API, the stored copy wins from then on.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SiteSettings {
+    pub site_name: String,
+    pub tagline: String,
+    /// Color scheme of the page and the one the theme toggle switches to.
+    pub default_theme: String,
+    pub secondary_theme: String,
+    /// Absolute url of the public server, used in feeds.
+    pub base_url: String,
+    pub locale: String,
+    pub menus: Menus,
+    pub footer_links: Vec<Link>,
+    /// Path of the request the settings were loaded for, marks the active
+    /// menu entries.
+    #[serde(skip)]
+    pub path: String,
+    /// Whether pages subscribe to the live reload events, see
+    /// [`Config::hot_reload`].
+    #[serde(skip)]
+    pub live_reload: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Link {
+    pub name: String,
+    pub url: String,
+}
+
+impl Default for SiteSettings {
+    fn default() -> Self {
+        SiteSettings {
+            site_name: "ISAALULA".to_string(),
+            tagline: String::new(),
+            default_theme: "mocha".to_string(),
+            secondary_theme: "latte".to_string(),
+            base_url: "http://localhost:8000".to_string(),
+            locale: "en".to_string(),
+            menus: Menus::default(),
+            footer_links: Vec::new(),
+            path: String::new(),
+            live_reload: false,
+        }
+    }
+}
+
+impl SiteSettings {
+    /// Template variables of the settings, merged under the page's own data.
+    pub fn template_data(&self) -> Map<String, Value> {
+        let mut data = Map::new();
+        data.insert("site_name".to_string(), Value::from(self.site_name.clone()));
+        data.insert("tagline".to_string(), Value::from(self.tagline.clone()));
+        data.insert("default_theme".to_string(), Value::from(self.default_theme.clone()));
+        data.insert("secondary_theme".to_string(), Value::from(self.secondary_theme.clone()));
+        data.insert("base_url".to_string(), Value::from(self.base_url.clone()));
+        data.insert("locale".to_string(), Value::from(self.locale.clone()));
+        data.insert("menus".to_string(), serde_json::json!(self.menus.get(&self.path)));
+        data.insert("footer_links".to_string(), serde_json::json!(self.footer_links));
+        data.insert("live_reload".to_string(), Value::from(self.live_reload));
+        data
+    }
+}
+
+#[async_trait]
+pub trait GetSettings {
+    /// The stored settings, or `fallback` while none were saved.
+    async fn site(&self, fallback: &SiteSettings) -> Result<SiteSettings, Error>;
+    async fn save_site(&self, settings: SiteSettings) -> Result<(), Error>;
+}
+
+#[async_trait]
+impl GetSettings for SettingsStore {
+    async fn site(&self, fallback: &SiteSettings) -> Result<SiteSettings, Error> {
+        Ok(self
+            .get(&SITE_KEY.to_string())
+            .await?
+            .unwrap_or_else(|| fallback.clone()))
+    }
+
+    async fn save_site(&self, settings: SiteSettings) -> Result<(), Error> {
+        self.insert(SITE_KEY.to_string(), settings).await
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for SiteSettings {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let rocket = request.rocket();
+        let (Some(store), Some(config)) = (rocket.state::<SettingsStore>(), rocket.state::<Config>())
+        else {
+            return Outcome::Error((Status::InternalServerError, ()));
+        };
+        let mut settings = match store.site(&config.site).await {
+            Ok(settings) => settings,
+            Err(_) => return Outcome::Error((Status::InternalServerError, ())),
+        };
+        if let Some(articles) = rocket.state::<ArticleRepository>() {
+            match page_menus(articles).await {
+                Ok(menus) => settings.menus = settings.menus.with(menus),
+                Err(_) => return Outcome::Error((Status::InternalServerError, ())),
+            }
+        }
+        settings.path = request.uri().path().to_string();
+        settings.live_reload = config.hot_reload;
+        Outcome::Success(settings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use ulid::Ulid;
+
+    fn db_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("myweb-settings-{}", Ulid::new()))
+    }
+
+    fn configured() -> SiteSettings {
+        SiteSettings {
+            site_name: "Configured".to_string(),
+            ..SiteSettings::default()
+        }
+    }
+
+    #[test]
+    fn fills_missing_config_values_with_the_defaults() {
+        let settings: SiteSettings =
+            serde_json::from_value(serde_json::json!({ "site_name": "Configured" })).unwrap();
+        assert_eq!(settings.site_name, "Configured");
+        assert_eq!(settings.locale, SiteSettings::default().locale);
+        assert_eq!(settings.default_theme, SiteSettings::default().default_theme);
+    }
+
+    #[tokio::test]
+    async fn uses_the_config_until_settings_are_saved() {
+        let dir = db_dir();
+        let store = SettingsStore::open(&dir, "settings").await.unwrap();
+        assert_eq!(store.site(&configured()).await.unwrap().site_name, "Configured");
+
+        let saved = SiteSettings {
+            site_name: "Saved".to_string(),
+            tagline: "Stored".to_string(),
+            ..SiteSettings::default()
+        };
+        store.save_site(saved).await.unwrap();
+        let settings = store.site(&configured()).await.unwrap();
+        assert_eq!(settings.site_name, "Saved");
+        assert_eq!(settings.tagline, "Stored");
+
+        // The stored copy survives a restart and still wins.
+        let store = SettingsStore::open(&dir, "settings").await.unwrap();
+        assert_eq!(store.site(&configured()).await.unwrap().site_name, "Saved");
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
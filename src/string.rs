@@ -8,7 +8,11 @@ impl TitleCase for &str {
     fn title_case(&self) -> String {
         self.split(' ')
             .map(|s| {
-                s.chars().next().unwrap().to_uppercase().collect::<String>() + s.get(1..).unwrap()
+                let mut chars = s.chars();
+                match chars.next() {
+                    Some(c) => c.to_uppercase().collect::<String>() + chars.as_str(),
+                    None => String::new(),
+                }
             })
             .collect::<Vec<_>>()
             .join(" ")
@@ -47,6 +51,39 @@ impl SnakeToTitleCase for String {
     }
 }
 
+pub trait Slugify {
+    fn slugify(&self) -> String;
+    fn is_slug(&self) -> bool;
+}
+
+impl Slugify for &str {
+    /// Lowercase snake case, the form used for article file names.
+    fn slugify(&self) -> String {
+        self.split(|c: char| !c.is_ascii_alphanumeric())
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+            .join("_")
+            .to_lowercase()
+    }
+
+    fn is_slug(&self) -> bool {
+        !self.is_empty()
+            && self
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    }
+}
+
+impl Slugify for String {
+    fn slugify(&self) -> String {
+        self.as_str().slugify()
+    }
+
+    fn is_slug(&self) -> bool {
+        self.as_str().is_slug()
+    }
+}
+
 pub trait StringCutter {
     fn cut_to_length(&self, max_length: usize) -> String;
 }
@@ -71,6 +108,19 @@ impl StringCutter for str {
     }
 }
 
+pub trait StableHash {
+    fn stable_hash(&self) -> u64;
+}
+
+impl StableHash for str {
+    fn stable_hash(&self) -> u64 {
+        // FNV-1a, std's hasher is not guaranteed to be stable between releases.
+        self.bytes().fold(0xcbf29ce484222325u64, |h, b| {
+            (h ^ b as u64).wrapping_mul(0x100000001b3)
+        })
+    }
+}
+
 impl Join<String> for String {
     fn join(&self, other: &String) -> String {
         format!("{}{}", self, other)
@@ -8,7 +8,8 @@ use std::{
 use async_trait::async_trait;
 use tokio::fs::read_to_string;
 
-use crate::db::mem::Data;
+use crate::db::{Data, Store};
+use crate::theme::Theme;
 use crate::Error;
 
 pub type TemplatePool = Data<Box<str>, Result<Arc<str>, String>>;
@@ -31,48 +32,43 @@ impl GetTemplate for TemplatePool {
     }
 }
 
-pub async fn read_template(
-    is_component: bool,
-    template: &str,
-    theme_dir: &Path,
-    theme: &str,
-) -> Result<Arc<str>, String> {
-    let template = match is_component {
-        true => format!("components/{}", template),
-        false => template.to_owned(),
-    };
-    read_to_string(
-        theme_dir
-            .join(theme)
-            .join("templates")
-            .join(template)
-            .with_extension("hbs"),
-    )
-    .await
-    .map_err(|e| e.to_string()).map(Arc::from)
+pub async fn read_template(path: &Path) -> Result<Arc<str>, String> {
+    read_to_string(path)
+        .await
+        .map_err(|e| e.to_string())
+        .map(Arc::from)
 }
 
+/// Reads every template and component listed in the manifest of `theme`.
+///
+/// Templates live in `<theme_dir>/<theme_name>/<templates_path>` and components
+/// in its `<components_path>` subdirectory, both as `<path>.hbs`, and are keyed
+/// by name. A listed file that can not be read or a name used twice fails.
 pub async fn load_all_templates(
     theme_dir: &Path,
-    theme: &str,
+    theme_name: &str,
+    theme: &Theme,
 ) -> Result<
     HashMap<Box<str>, Result<Arc<str>, String>>,
     Error,
 > {
-    let templates = vec![
-        ("layout", true),
-        ("navbar", true),
-        ("overlay", true),
-        ("blog", true),
-        ("404", true),
-        ("default", false),
-    ];
-
     let mut template_pool = HashMap::new();
 
-    for (name, is_component) in templates {
-        let content = read_template(is_component, name, theme_dir, theme).await;
-        template_pool.insert(name.to_string().into_boxed_str(), content);
+    for (name, path) in theme.template_files(&theme_dir.join(theme_name)) {
+        let name: Box<str> = name.into();
+        if template_pool.contains_key(&name) {
+            return Err(format!("Theme {} lists template {} twice", theme.name, name).into());
+        }
+        let content = read_template(&path).await.map_err(|e| {
+            format!(
+                "Theme {} lists template {} but {} can not be read: {}",
+                theme.name,
+                name,
+                path.display(),
+                e
+            )
+        })?;
+        template_pool.insert(name, Ok(content));
     }
 
     Ok(template_pool)
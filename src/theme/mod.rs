@@ -0,0 +1,561 @@
+use schemars::{schema_for, JsonSchema};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use tokio::fs::{read_to_string, try_exists};
+use crate::Error;
+
+mod registry;
+pub use registry::{ActiveTheme, InstalledTheme, ThemeRegistry};
+
+/// Manifest file names a theme directory is searched for, in that order.
+const MANIFESTS: [&str; 2] = ["meta.toml", "meta.json"];
+
+/// A theme manifest, `meta.toml` or `meta.json`, see `theme/meta.schema.json`.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct Theme {
+    pub name: String,
+    pub version: String,
+    pub author: String,
+    pub static_path: PathBuf,
+    pub templates_path: PathBuf,
+    pub templates: Templates,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct Templates {
+    pub components_path: PathBuf,
+    pub templates: Vec<Template>,
+    pub components: Vec<Template>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct Template {
+    pub name: String,
+    pub path: PathBuf,
+    pub components: Option<Vec<String>>,
+    pub override_components: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "variables")]
+    #[schemars(with = "Option<Variables>")]
+    pub variables: Option<Vec<(Variable, String)>>,
+    /// Defaults of variables declared in the tree, the ones of templates closer
+    /// to the page template win.
+    pub override_variables: Option<Map<String, Value>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq)]
+pub enum Variable {
+    Bool,
+    String,
+    Value,
+    Raw,
+}
+
+/// Variables as manifests declare them, a list of `[type, name]` pairs or a
+/// map of name to type.
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum Variables {
+    List(Vec<(Variable, String)>),
+    Map(BTreeMap<String, Variable>),
+}
+
+fn variables<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Vec<(Variable, String)>>, D::Error> {
+    Ok(Option::<Variables>::deserialize(deserializer)?.map(|variables| match variables {
+        Variables::List(list) => list,
+        Variables::Map(map) => map.into_iter().map(|(name, variable)| (variable, name)).collect(),
+    }))
+}
+
+impl Variable {
+    /// `Value` takes anything, `null` included, `Raw` is an HTML string.
+    pub fn accepts(&self, value: &Value) -> bool {
+        match self {
+            Variable::Bool => value.is_boolean(),
+            Variable::String | Variable::Raw => value.is_string(),
+            Variable::Value => true,
+        }
+    }
+}
+
+impl Theme {
+    /// Reads a TOML or JSON manifest, by the extension of `path`.
+    pub async fn read(path: &Path) -> Result<Theme, Error> {
+        let theme = read_to_string(path)
+            .await
+            .map_err(|e| format!("Can not read theme manifest {}: {}", path.display(), e))?;
+        let parsed = match path.extension().and_then(OsStr::to_str) {
+            Some("toml") => toml::from_str(&theme).map_err(|e| e.to_string()),
+            Some("json") => serde_json::from_str(&theme).map_err(|e| e.to_string()),
+            _ => Err("expected a .toml or .json file".to_string()),
+        };
+        parsed.map_err(|e| format!("Invalid theme manifest {}: {}", path.display(), e).into())
+    }
+
+    /// Reads the manifest of the theme in `dir`, `meta.toml` or else `meta.json`.
+    pub async fn find(dir: &Path) -> Result<Theme, Error> {
+        for manifest in MANIFESTS {
+            let path = dir.join(manifest);
+            if try_exists(&path).await? {
+                return Theme::read(&path).await;
+            }
+        }
+        Err(format!("Theme {} has no {}", dir.display(), MANIFESTS.join(" or ")).into())
+    }
+
+    /// JSON Schema of the manifests, written to `theme/meta.schema.json` by
+    /// `myweb --theme-schema`.
+    pub fn schema() -> Result<String, Error> {
+        Ok(serde_json::to_string_pretty(&schema_for!(Theme))?)
+    }
+
+    pub fn template(&self, name: &str) -> Option<&Template> {
+        self.templates.templates.iter().find(|t| t.name == name)
+    }
+
+    pub fn component(&self, name: &str) -> Option<&Template> {
+        self.templates.components.iter().find(|t| t.name == name)
+    }
+
+    /// Names and files of the templates and components, the theme living in
+    /// `dir`.
+    pub fn template_files<'a>(&'a self, dir: &Path) -> impl Iterator<Item = (&'a str, PathBuf)> {
+        let templates_dir = dir.join(&self.templates_path);
+        let components_dir = templates_dir.join(&self.templates.components_path);
+        let templates = self.templates.templates.iter().map(move |t| {
+            (t.name.as_str(), templates_dir.join(&t.path).with_extension("hbs"))
+        });
+        let components = self.templates.components.iter().map(move |c| {
+            (c.name.as_str(), components_dir.join(&c.path).with_extension("hbs"))
+        });
+        templates.chain(components)
+    }
+
+    /// Resolves the partials and variables of the page template `template`.
+    ///
+    /// The partials every template and component lists under `components` are
+    /// resolved to the component of the same name, unless `overrides` maps
+    /// them to another one, or a template above them in the tree lists them
+    /// under `override_components` and so fills them inline, like `default`
+    /// fills the `content` slot of its parent layout. Components used under
+    /// another name are registered under their own name too, for dynamic
+    /// partials like `{{> (lookup this "parent")}}`.
+    pub fn resolve<'a>(
+        &'a self,
+        template: &str,
+        overrides: &[(&str, &str)],
+    ) -> Result<ComponentTree<'a>, Error> {
+        let page = self
+            .template(template)
+            .ok_or_else(|| format!("Template {} is not in the theme", template))?;
+        let mut resolver = Resolver {
+            theme: self,
+            overrides,
+            path: Vec::new(),
+            expanded: Vec::new(),
+            filled: Vec::new(),
+            partials: vec![(page.name.clone(), page.name.clone())],
+        };
+        resolver.visit(page)?;
+        let Resolver { expanded, filled, partials, .. } = resolver;
+
+        for (template, _) in &expanded {
+            for slot in template.override_components.iter().flatten() {
+                if !filled.contains(&(template.name.as_str(), slot.as_str())) {
+                    return Err(format!(
+                        "{} overrides {} but no template it includes has that slot",
+                        template.name, slot
+                    )
+                    .into());
+                }
+            }
+        }
+
+        let mut variables: Vec<(Variable, &str)> = Vec::new();
+        for (template, _) in &expanded {
+            for (variable, name) in template.variables.iter().flatten() {
+                match variables.iter().find(|(_, n)| n == name) {
+                    Some((declared, _)) if declared != variable => {
+                        return Err(format!(
+                            "{} declares {} as {:?}, another template as {:?}",
+                            template.name, name, variable, declared
+                        )
+                        .into())
+                    }
+                    Some(_) => {}
+                    None => variables.push((*variable, name)),
+                }
+            }
+        }
+
+        // Deepest first, so the defaults of the templates closer to the page win.
+        let mut by_depth = expanded;
+        by_depth.sort_by_key(|(_, depth)| Reverse(*depth));
+        let mut defaults = Map::new();
+        for (template, _) in by_depth {
+            for (name, value) in template.override_variables.iter().flatten() {
+                match variables.iter().find(|(_, n)| n == name) {
+                    Some((variable, _)) if variable.accepts(value) => {
+                        defaults.insert(name.clone(), value.clone());
+                    }
+                    Some((variable, _)) => {
+                        return Err(format!(
+                            "{} sets {} to {}, it should be {:?}",
+                            template.name, name, value, variable
+                        )
+                        .into())
+                    }
+                    None => {
+                        return Err(format!(
+                            "{} sets {} but no template declares it",
+                            template.name, name
+                        )
+                        .into())
+                    }
+                }
+            }
+        }
+
+        Ok(ComponentTree { partials, variables, defaults })
+    }
+}
+
+/// A page template with its partials resolved, see [`Theme::resolve`].
+#[derive(Debug)]
+pub struct ComponentTree<'a> {
+    /// Partial name and template name pairs to register.
+    pub partials: Vec<(String, String)>,
+    /// Variables declared by the registered templates.
+    pub variables: Vec<(Variable, &'a str)>,
+    /// Values of the variables the render data may leave out.
+    pub defaults: Map<String, Value>,
+}
+
+struct Resolver<'a, 'o> {
+    theme: &'a Theme,
+    overrides: &'o [(&'o str, &'o str)],
+    /// Templates from the page template down to the one being visited.
+    path: Vec<&'a Template>,
+    /// Visited templates and their depth below the page template.
+    expanded: Vec<(&'a Template, usize)>,
+    /// Template and slot pairs filled inline.
+    filled: Vec<(&'a str, &'a str)>,
+    partials: Vec<(String, String)>,
+}
+
+impl<'a> Resolver<'a, '_> {
+    fn visit(&mut self, template: &'a Template) -> Result<(), Error> {
+        if let Some(start) = self.path.iter().position(|t| t.name == template.name) {
+            let cycle: Vec<&str> = self.path[start..]
+                .iter()
+                .map(|t| t.name.as_str())
+                .chain([template.name.as_str()])
+                .collect();
+            return Err(format!("Template cycle: {}", cycle.join(" -> ")).into());
+        }
+        if self.expanded.iter().any(|(t, _)| t.name == template.name) {
+            return Ok(());
+        }
+        self.expanded.push((template, self.path.len()));
+        self.path.push(template);
+        for partial in template.components.iter().flatten() {
+            let owner = self
+                .path
+                .iter()
+                .find(|t| t.override_components.iter().flatten().any(|c| c == partial));
+            if let Some(owner) = owner {
+                self.filled.push((owner.name.as_str(), partial.as_str()));
+                continue;
+            }
+            let file = self
+                .overrides
+                .iter()
+                .find(|(p, _)| *p == partial)
+                .map_or(partial.as_str(), |(_, f)| *f);
+            let component = self.theme.component(file).ok_or_else(|| {
+                format!("Component {} for partial {} is not in the theme", file, partial)
+            })?;
+            for pair in [(partial, &component.name), (&component.name, &component.name)] {
+                if !self.partials.iter().any(|(p, _)| p == pair.0) {
+                    self.partials.push((pair.0.clone(), pair.1.clone()));
+                }
+            }
+            self.visit(component)?;
+        }
+        self.path.pop();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn theme(templates: &str) -> Theme {
+        let manifest = format!(
+            r#"
+            name = "Test"
+            version = "0.1.0"
+            author = "Test"
+            static_path = "static"
+            templates_path = "templates"
+
+            [templates]
+            components_path = "components"
+            {}
+            "#,
+            templates
+        );
+        toml::from_str(&manifest).unwrap()
+    }
+
+    #[test]
+    fn reports_a_cycle() {
+        let theme = theme(
+            r#"
+            [[templates.templates]]
+            name = "page"
+            path = "page"
+            components = ["a"]
+
+            [[templates.components]]
+            name = "a"
+            path = "a"
+            components = ["b"]
+
+            [[templates.components]]
+            name = "b"
+            path = "b"
+            components = ["a"]
+            "#,
+        );
+        let error = theme.resolve("page", &[]).unwrap_err();
+        assert_eq!(error.to_string(), "Template cycle: a -> b -> a");
+    }
+
+    #[test]
+    fn fills_override_slots_inline() {
+        let theme = theme(
+            r#"
+            [[templates.templates]]
+            name = "page"
+            path = "page"
+            components = ["layout"]
+            override_components = ["content"]
+
+            [[templates.components]]
+            name = "layout"
+            path = "layout"
+            components = ["content"]
+            "#,
+        );
+        let tree = theme.resolve("page", &[]).unwrap();
+        let partials: Vec<&str> = tree.partials.iter().map(|(p, _)| p.as_str()).collect();
+        assert_eq!(partials, ["page", "layout"]);
+    }
+
+    #[test]
+    fn reports_an_unfilled_override_slot() {
+        let theme = theme(
+            r#"
+            [[templates.templates]]
+            name = "page"
+            path = "page"
+            components = ["layout"]
+            override_components = ["sidebar"]
+
+            [[templates.components]]
+            name = "layout"
+            path = "layout"
+            "#,
+        );
+        let error = theme.resolve("page", &[]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "page overrides sidebar but no template it includes has that slot"
+        );
+    }
+
+    #[test]
+    fn reports_conflicting_variable_types() {
+        let theme = theme(
+            r#"
+            [[templates.templates]]
+            name = "page"
+            path = "page"
+            components = ["layout"]
+            variables = { title = "Bool" }
+
+            [[templates.components]]
+            name = "layout"
+            path = "layout"
+            variables = [ ["String", "title"] ]
+            "#,
+        );
+        let error = theme.resolve("page", &[]).unwrap_err();
+        assert_eq!(error.to_string(), "layout declares title as String, another template as Bool");
+    }
+
+    #[test]
+    fn defaults_closer_to_the_page_win() {
+        let theme = theme(
+            r#"
+            [[templates.templates]]
+            name = "page"
+            path = "page"
+            components = ["layout"]
+            override_variables = { layout_min = true }
+
+            [[templates.components]]
+            name = "layout"
+            path = "layout"
+            components = ["footer"]
+            variables = { layout_min = "Bool", links = "Value" }
+            override_variables = { layout_min = false, links = [] }
+
+            [[templates.components]]
+            name = "footer"
+            path = "footer"
+            override_variables = { links = ["/about"] }
+            "#,
+        );
+        let tree = theme.resolve("page", &[]).unwrap();
+        assert_eq!(tree.defaults["layout_min"], json!(true));
+        assert_eq!(tree.defaults["links"], json!([]));
+    }
+
+    #[test]
+    fn reports_a_default_of_the_wrong_type() {
+        let theme = theme(
+            r#"
+            [[templates.templates]]
+            name = "page"
+            path = "page"
+            components = ["layout"]
+            override_variables = { layout_min = "yes" }
+
+            [[templates.components]]
+            name = "layout"
+            path = "layout"
+            variables = { layout_min = "Bool" }
+            "#,
+        );
+        let error = theme.resolve("page", &[]).unwrap_err();
+        assert_eq!(error.to_string(), r#"page sets layout_min to "yes", it should be Bool"#);
+    }
+
+    fn theme_dir(files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("myweb-theme-{}", ulid::Ulid::new()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, source) in files {
+            std::fs::write(dir.join(name), source).unwrap();
+        }
+        dir
+    }
+
+    const META_TOML: &str = r#"
+        name = "Test"
+        version = "0.1.0"
+        author = "Test"
+        static_path = "static"
+        templates_path = "templates"
+
+        [templates]
+        components_path = "components"
+
+        [[templates.templates]]
+        name = "page"
+        path = "page"
+        variables = [ ["Raw", "article"], ["Bool", "layout_min"] ]
+
+        [[templates.components]]
+        name = "layout"
+        path = "layout"
+        variables = { title = "String", links = "Value" }
+        "#;
+
+    const META_JSON: &str = r#"{
+        "name": "Test",
+        "version": "0.1.0",
+        "author": "Test",
+        "static_path": "static",
+        "templates_path": "templates",
+        "templates": {
+            "components_path": "components",
+            "templates": [
+                { "name": "page", "path": "page", "variables": [["Raw", "article"], ["Bool", "layout_min"]] }
+            ],
+            "components": [
+                { "name": "layout", "path": "layout", "variables": { "title": "String", "links": "Value" } }
+            ]
+        }
+    }"#;
+
+    fn variables(template: &Template) -> Vec<(Variable, &str)> {
+        let variables = template.variables.as_ref().unwrap();
+        variables.iter().map(|(v, n)| (*v, n.as_str())).collect()
+    }
+
+    #[tokio::test]
+    async fn reads_toml_and_json_manifests_alike() {
+        let dir = theme_dir(&[("meta.toml", META_TOML), ("meta.json", META_JSON)]);
+        for manifest in MANIFESTS {
+            let theme = Theme::read(&dir.join(manifest)).await.unwrap();
+            assert_eq!(theme.name, "Test", "{}", manifest);
+            assert_eq!(theme.templates.components_path, Path::new("components"));
+            // Both shapes of `variables` end up as `[type, name]` pairs, maps
+            // ordered by name.
+            let page = theme.template("page").unwrap();
+            assert_eq!(variables(page), [(Variable::Raw, "article"), (Variable::Bool, "layout_min")]);
+            let layout = theme.component("layout").unwrap();
+            assert_eq!(variables(layout), [(Variable::Value, "links"), (Variable::String, "title")]);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn finds_meta_toml_before_meta_json() {
+        let json_only = theme_dir(&[("meta.json", META_JSON)]);
+        assert_eq!(Theme::find(&json_only).await.unwrap().name, "Test");
+
+        let both = theme_dir(&[("meta.toml", &META_TOML.replace("\"Test\"", "\"Toml\"")), ("meta.json", META_JSON)]);
+        assert_eq!(Theme::find(&both).await.unwrap().name, "Toml");
+
+        let none = theme_dir(&[("meta.yaml", "name: Test")]);
+        let error = Theme::find(&none).await.unwrap_err().to_string();
+        assert!(error.ends_with("has no meta.toml or meta.json"), "{}", error);
+        let error = Theme::read(&none.join("meta.yaml")).await.unwrap_err().to_string();
+        assert!(error.ends_with("expected a .toml or .json file"), "{}", error);
+        for dir in [json_only, both, none] {
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn reports_where_a_manifest_is_invalid() {
+        let dir = theme_dir(&[("meta.json", r#"{ "name": "Test" }"#)]);
+        let error = Theme::read(&dir.join("meta.json")).await.unwrap_err().to_string();
+        assert!(error.starts_with("Invalid theme manifest"), "{}", error);
+        assert!(error.contains("version"), "{}", error);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn reads_both_manifests_of_the_bundled_theme() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("theme/default");
+        let toml = Theme::read(&dir.join("meta.toml")).await.unwrap();
+        let json = Theme::read(&dir.join("meta.json")).await.unwrap();
+        assert!(toml.template("default").is_some());
+        let names = |theme: &Theme| {
+            let templates = theme.templates.templates.iter().chain(&theme.templates.components);
+            templates.map(|t| t.name.clone()).collect::<Vec<_>>()
+        };
+        assert_eq!(names(&toml), names(&json));
+    }
+}
@@ -0,0 +1,205 @@
+use std::path::Path;
+use std::sync::Arc;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use serde::Serialize;
+use tokio::fs::read_dir;
+use tokio::sync::RwLock;
+use super::Theme;
+use crate::config::Config;
+use crate::db::{Db, FileData, Store};
+use crate::render::RegistryCache;
+use crate::template::{load_all_templates, TemplatePool};
+use crate::Error;
+
+/// Name of the theme an admin switched to, under [`ACTIVE_KEY`].
+pub type ThemeStore = FileData<String, String>;
+const ACTIVE_KEY: &str = "active";
+
+/// The theme pages are rendered with, manifest and templates taken together so
+/// a request never mixes two themes.
+#[derive(Clone)]
+pub struct ActiveTheme {
+    /// Directory of the theme below `theme_dir`.
+    pub name: Arc<str>,
+    pub manifest: Arc<Theme>,
+    pub templates: TemplatePool,
+    pub registries: RegistryCache,
+    /// Counts the activations and reloads, pages rendered with an older
+    /// generation are not cached, see [`crate::cache::PageCache::get`].
+    pub generation: u64,
+}
+
+/// A directory of `theme_dir` with a valid manifest.
+#[derive(Serialize)]
+pub struct InstalledTheme {
+    pub name: String,
+    pub active: bool,
+    pub manifest: Theme,
+}
+
+/// The themes installed below `theme_dir` and the active one, shared by the
+/// public and admin servers.
+#[derive(Clone)]
+pub struct ThemeRegistry {
+    theme_dir: Arc<Path>,
+    store: ThemeStore,
+    active: Arc<RwLock<ActiveTheme>>,
+}
+
+impl ActiveTheme {
+    /// Reads the manifest and every template of the theme `name`.
+    pub async fn load(theme_dir: &Path, name: &str) -> Result<ActiveTheme, Error> {
+        if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+            return Err(format!("Invalid theme name {}", name).into());
+        }
+        let manifest = Theme::find(&theme_dir.join(name)).await?;
+        let templates = load_all_templates(theme_dir, name, &manifest).await?;
+        Ok(ActiveTheme {
+            name: name.into(),
+            manifest: Arc::new(manifest),
+            templates: TemplatePool::from(false, templates),
+            registries: RegistryCache::new(false),
+            generation: 0,
+        })
+    }
+}
+
+impl ThemeRegistry {
+    /// Activates the theme last switched to, or `config.theme` when there is
+    /// none or it does not load anymore.
+    pub async fn open(config: &Config, db: &Db) -> Result<ThemeRegistry, Error> {
+        let store: ThemeStore = db.open("theme").await?;
+        let theme_dir = config.theme_dir.clone();
+        let active = match store.get(&ACTIVE_KEY.to_string()).await? {
+            Some(name) => match ActiveTheme::load(&theme_dir, &name).await {
+                Ok(active) => active,
+                Err(e) => {
+                    rocket::warn!("Theme {} failed to load, using {}: {}", name, config.theme, e);
+                    ActiveTheme::load(&theme_dir, &config.theme).await?
+                }
+            },
+            None => ActiveTheme::load(&theme_dir, &config.theme).await?,
+        };
+        Ok(ThemeRegistry {
+            theme_dir,
+            store,
+            active: Arc::new(RwLock::new(active)),
+        })
+    }
+
+    pub async fn active(&self) -> ActiveTheme {
+        self.active.read().await.clone()
+    }
+
+    /// Directories of `theme_dir` whose manifest parses, by name.
+    pub async fn installed(&self) -> Result<Vec<InstalledTheme>, Error> {
+        let active = self.active.read().await.name.clone();
+        let mut themes = Vec::new();
+        let mut entries = read_dir(&self.theme_dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if !entry.file_type().await?.is_dir() {
+                continue;
+            }
+            let name = entry.file_name().to_string_lossy().to_string();
+            if let Ok(manifest) = Theme::find(&entry.path()).await {
+                themes.push(InstalledTheme {
+                    active: *name == *active,
+                    name,
+                    manifest,
+                });
+            }
+        }
+        themes.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(themes)
+    }
+
+    /// Reads the active theme again after its files changed, a theme that
+    /// does not load anymore stays in place. Like [`Self::activate`] the
+    /// reload starts a new generation.
+    ///
+    /// Returns `None` when a theme was activated while this one loaded, the
+    /// activated theme is left in place then.
+    pub async fn reload(&self) -> Result<Option<ActiveTheme>, Error> {
+        let (name, generation) = {
+            let active = self.active.read().await;
+            (active.name.clone(), active.generation)
+        };
+        let mut theme = ActiveTheme::load(&self.theme_dir, &name).await?;
+        let mut active = self.active.write().await;
+        if active.generation != generation || active.name != name {
+            return Ok(None);
+        }
+        theme.generation = generation + 1;
+        *active = theme.clone();
+        Ok(Some(theme))
+    }
+
+    /// Makes `name` the active theme, also after a restart.
+    ///
+    /// The theme is read completely before the swap, a broken one leaves the
+    /// active theme in place. The swap starts a new generation, so the page
+    /// cache neither keeps nor serves pages of the old theme.
+    pub async fn activate(&self, name: &str) -> Result<ActiveTheme, Error> {
+        let mut theme = ActiveTheme::load(&self.theme_dir, name).await?;
+        self.store.insert(ACTIVE_KEY.to_string(), name.to_string()).await?;
+        let mut active = self.active.write().await;
+        theme.generation = active.generation + 1;
+        *active = theme.clone();
+        Ok(theme)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ActiveTheme {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match request.rocket().state::<ThemeRegistry>() {
+            Some(registry) => Outcome::Success(registry.active().await),
+            None => Outcome::Error((Status::InternalServerError, ())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ulid::Ulid;
+
+    async fn registry() -> (ThemeRegistry, Arc<Path>) {
+        let db_dir: Arc<Path> = std::env::temp_dir().join(format!("myweb-themes-{}", Ulid::new())).into();
+        let config = Config {
+            theme: "default".into(),
+            theme_dir: Path::new(env!("CARGO_MANIFEST_DIR")).join("theme").into(),
+            db_dir: db_dir.clone(),
+            ..Config::default()
+        };
+        let registry = ThemeRegistry::open(&config, &Db::new(db_dir.clone())).await.unwrap();
+        (registry, db_dir)
+    }
+
+    #[tokio::test]
+    async fn starts_a_new_generation_on_reload_and_activation() {
+        let (registry, db_dir) = registry().await;
+        assert_eq!(registry.active().await.generation, 0);
+
+        let reloaded = registry.reload().await.unwrap().unwrap();
+        assert_eq!(reloaded.generation, 1);
+        assert_eq!(registry.active().await.generation, 1);
+
+        registry.activate("default").await.unwrap();
+        assert_eq!(registry.active().await.generation, 2);
+        tokio::fs::remove_dir_all(&db_dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn refuses_names_outside_the_theme_dir() {
+        let (registry, db_dir) = registry().await;
+        for name in ["", "..", "default/../default"] {
+            assert!(registry.activate(name).await.is_err(), "{}", name);
+        }
+        assert_eq!(registry.active().await.generation, 0);
+        tokio::fs::remove_dir_all(&db_dir).await.unwrap();
+    }
+}